@@ -0,0 +1,254 @@
+//! The `pahole` command, which lays out the items of a Rust source file like the attribute does
+//! for the items it's applied to, printing the report to stdout.
+//!
+//! With `--watch`, it instead checks its files for changes twice a second, reporting the items
+//! that were laid out again. Each file's items are then put in a module named after the file.
+
+use std::{path::PathBuf, process, thread, time::Duration};
+
+use pahole_core::{incremental::Analyzer, layout, options::Format, report};
+
+const USAGE: &str =
+    "usage: pahole [--format table|compact] [--pointer-width 16|32|64] [--features A,B] [--json] FILE\n\
+    \x20      pahole [options] --watch FILE...";
+
+#[derive(Debug, Eq, PartialEq)]
+struct Args {
+    /// Only one, unless `watch` is set.
+    files: Vec<String>,
+    format: Format,
+    /// Overrides the host's pointer width, in bits.
+    pointer_width: Option<usize>,
+    /// The enabled features to evaluate `#[cfg(feature = "...")]` with, like the `features` option.
+    features: Option<Vec<String>>,
+    /// Prints the layouts as JSON, like the `emit_json` option, instead of the report.
+    json: bool,
+    watch: bool,
+}
+
+/// Parses the command's arguments, not including the program name. The flags mirror the attribute
+/// options of the same names.
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Args, String> {
+    let mut args = args.into_iter();
+    let mut files = Vec::new();
+    let mut format = Format::Table;
+    let mut pointer_width = None;
+    let mut features = None;
+    let mut json = false;
+    let mut watch = false;
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("`{}` needs a value", arg))
+        };
+        match arg.as_str() {
+            "--format" => {
+                format = match value()?.as_str() {
+                    "table" => Format::Table,
+                    "compact" => Format::Compact,
+                    x => return Err(format!("unknown format `{}`", x)),
+                }
+            }
+            "--pointer-width" => {
+                pointer_width = match value()?.as_str() {
+                    "16" => Some(16),
+                    "32" => Some(32),
+                    "64" => Some(64),
+                    x => return Err(format!("unsupported pointer width `{}`", x)),
+                }
+            }
+            "--features" => {
+                let value = value()?;
+                features.get_or_insert_with(Vec::new).extend(
+                    value
+                        .split(',')
+                        .filter(|x| !x.is_empty())
+                        .map(str::to_string),
+                );
+            }
+            "--json" => json = true,
+            "--watch" => watch = true,
+            x if x.starts_with('-') => return Err(format!("unknown flag `{}`", x)),
+            _ => files.push(arg),
+        }
+    }
+    match files.len() {
+        0 => return Err("expected a file".to_string()),
+        1 => {}
+        _ if !watch => return Err("expected only one file without `--watch`".to_string()),
+        _ => {}
+    }
+    Ok(Args {
+        files,
+        format,
+        pointer_width,
+        features,
+        json,
+        watch,
+    })
+}
+
+/// The report for the file named in `args`.
+fn run(args: &Args) -> Result<String, String> {
+    let source = std::fs::read_to_string(&args.files[0])
+        .map_err(|err| format!("could not read `{}`: {}", args.files[0], err))?;
+    let located = |err: syn::Error| {
+        let start = err.span().start();
+        format!(
+            "{}:{}:{}: {}",
+            args.files[0],
+            start.line,
+            start.column + 1,
+            err
+        )
+    };
+    let file = syn::parse_file(&source).map_err(located)?;
+    let target = args
+        .pointer_width
+        .map(|pointer_width| layout::Target { pointer_width });
+    let features = args.features.as_ref().map(|x| x.iter().cloned().collect());
+    let layouts = pahole_core::analyze_syn_file(&file, target, features).map_err(located)?;
+    format_layouts(args, &layouts)
+}
+
+/// Reports the items of the files named in `args` each time they change, until interrupted.
+fn watch(args: &Args) -> Result<(), String> {
+    let mut analyzer = Analyzer::new();
+    if let Some(pointer_width) = args.pointer_width {
+        analyzer.set_target(layout::Target { pointer_width });
+    }
+    if let Some(features) = &args.features {
+        analyzer.set_features(features.iter().cloned().collect());
+    }
+    let mut modified = vec![None; args.files.len()];
+    loop {
+        let mut changed = Vec::new();
+        for (file, seen) in args.files.iter().zip(&mut modified) {
+            let time = std::fs::metadata(file)
+                .and_then(|x| x.modified())
+                .map_err(|err| format!("could not read `{}`: {}", file, err))?;
+            if *seen != Some(time) {
+                *seen = Some(time);
+                changed.push(PathBuf::from(file));
+            }
+        }
+        if !changed.is_empty() {
+            // A file that fails to parse is tried again once it's saved again.
+            match analyzer.analyze_incremental(&changed) {
+                Ok(layouts) => print!("{}", format_layouts(args, &layouts)?),
+                Err(err) => eprintln!("error: {}", err),
+            }
+        }
+        thread::sleep(Duration::from_millis(500));
+    }
+}
+
+fn format_layouts(args: &Args, layouts: &[layout::ItemLayout]) -> Result<String, String> {
+    if args.json {
+        return json(layouts);
+    }
+    let mut out = String::new();
+    for layout in layouts {
+        match args.format {
+            Format::Table => {
+                out += &report::header(layout, None);
+                out += &report::table(layout);
+            }
+            Format::Compact => out += &report::compact(layout),
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(feature = "serde")]
+fn json(layouts: &[layout::ItemLayout]) -> Result<String, String> {
+    Ok(pahole_core::golden::to_json(layouts) + "\n")
+}
+
+#[cfg(not(feature = "serde"))]
+fn json(_: &[layout::ItemLayout]) -> Result<String, String> {
+    Err("`--json` needs the `serde` feature".to_string())
+}
+
+fn main() {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(x) => x,
+        Err(err) => {
+            eprintln!("error: {}\n{}", err, USAGE);
+            process::exit(2);
+        }
+    };
+    let result = if args.watch {
+        watch(&args).map(|()| String::new())
+    } else {
+        run(&args)
+    };
+    match result {
+        Ok(report) => print!("{}", report),
+        Err(err) => {
+            eprintln!("error: {}", err);
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cli_reports_the_items_of_a_file() {
+        let args = |x: &[&str]| parse_args(x.iter().map(|y| y.to_string()));
+        assert_eq!(
+            args(&["--format", "compact", "--pointer-width", "32", "a.rs"]),
+            Ok(Args {
+                files: vec!["a.rs".to_string()],
+                format: Format::Compact,
+                pointer_width: Some(32),
+                features: None,
+                json: false,
+                watch: false,
+            })
+        );
+        assert_eq!(
+            args(&["--watch", "a.rs", "b.rs"]).map(|x| (x.files, x.watch)),
+            Ok((vec!["a.rs".to_string(), "b.rs".to_string()], true))
+        );
+        assert_eq!(
+            args(&["--features", "a,b", "--features", "c", "a.rs"]).map(|x| x.features),
+            Ok(Some(vec![
+                "a".to_string(),
+                "b".to_string(),
+                "c".to_string()
+            ]))
+        );
+        assert_eq!(args(&["--format"]).unwrap_err(), "`--format` needs a value");
+        assert_eq!(
+            args(&["--pointer-width", "8", "a.rs"]).unwrap_err(),
+            "unsupported pointer width `8`"
+        );
+        assert_eq!(
+            args(&["a.rs", "b.rs"]).unwrap_err(),
+            "expected only one file without `--watch`"
+        );
+        assert_eq!(args(&[]).unwrap_err(), "expected a file");
+
+        let path = std::env::temp_dir().join(format!("pahole-cli-{}.rs", std::process::id()));
+        let file = path.to_str().unwrap();
+        std::fs::write(&path, "struct S { flag: bool, len: usize }\n").unwrap();
+        let report = run(&args(&["--format", "compact", "--pointer-width", "16", file]).unwrap());
+        std::fs::write(&path, "struct S { flag: bool }\nstruct T(Missing);\n").unwrap();
+        let error = run(&args(&[file]).unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            report.unwrap(),
+            "S: size=4 align=2 pad=1 holes=0\n\
+             S.len @0 size=2 align=2\n\
+             S.flag @2 size=1 align=1\n"
+        );
+        assert_eq!(
+            error.unwrap_err(),
+            format!("{}:2:10: pahole could not find the type `Missing`", file)
+        );
+    }
+}
@@ -0,0 +1,149 @@
+//! Evaluation of `#[cfg(...)]` on the fields and items inside an annotated item.
+//!
+//! Attribute macros see their input before rustc strips disabled code from it, so this decides
+//! what rustc would keep. Cargo doesn't tell proc macros which features are enabled, so `feature`
+//! is only evaluated against the ones listed with the `features` option, and is an error without
+//! it; `target_pointer_width` comes from the target being analyzed. Other predicates are errors
+//! rather than guesses.
+
+use std::collections::HashSet;
+
+use syn::{
+    parse::Error, punctuated::Punctuated, Attribute, Fields, Item, Lit, Meta, NestedMeta, Token,
+};
+
+use crate::layout::Target;
+
+fn string_value(lit: &Lit) -> Result<String, Error> {
+    match lit {
+        Lit::Str(x) => Ok(x.value()),
+        _ => Err(Error::new_spanned(lit, "expected a string")),
+    }
+}
+
+fn predicate(
+    meta: &NestedMeta,
+    target: &Target,
+    features: Option<&HashSet<String>>,
+) -> Result<bool, Error> {
+    match meta {
+        NestedMeta::Meta(Meta::NameValue(x)) if x.path.is_ident("feature") => {
+            let features = features.ok_or_else(|| {
+                Error::new_spanned(
+                    meta,
+                    r#"pahole can't tell which features are enabled; list them with `features("...")`"#,
+                )
+            })?;
+            Ok(features.contains(&string_value(&x.lit)?))
+        }
+        NestedMeta::Meta(Meta::NameValue(x)) if x.path.is_ident("target_pointer_width") => {
+            Ok(string_value(&x.lit)? == target.pointer_width.to_string())
+        }
+        NestedMeta::Meta(Meta::List(x)) if x.path.is_ident("all") => {
+            for nested in &x.nested {
+                if !predicate(nested, target, features)? {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }
+        NestedMeta::Meta(Meta::List(x)) if x.path.is_ident("any") => {
+            for nested in &x.nested {
+                if predicate(nested, target, features)? {
+                    return Ok(true);
+                }
+            }
+            Ok(false)
+        }
+        NestedMeta::Meta(Meta::List(x)) if x.path.is_ident("not") && x.nested.len() == 1 => {
+            Ok(!predicate(&x.nested[0], target, features)?)
+        }
+        _ => Err(Error::new_spanned(
+            meta,
+            "pahole can only evaluate `feature` and `target_pointer_width` in `cfg`",
+        )),
+    }
+}
+
+/// Whether every `cfg` attribute in `attrs` holds.
+pub fn enabled(
+    attrs: &[Attribute],
+    target: &Target,
+    features: Option<&HashSet<String>>,
+) -> Result<bool, Error> {
+    for attr in attrs.iter().filter(|x| x.path.is_ident("cfg")) {
+        match attr.parse_meta()? {
+            Meta::List(x) if x.nested.len() == 1 => {
+                if !predicate(&x.nested[0], target, features)? {
+                    return Ok(false);
+                }
+            }
+            _ => return Err(Error::new_spanned(attr, "expected `#[cfg(predicate)]`")),
+        }
+    }
+    Ok(true)
+}
+
+fn retain<T>(
+    items: &mut Punctuated<T, Token![,]>,
+    attrs: impl Fn(&T) -> &[Attribute],
+    target: &Target,
+    features: Option<&HashSet<String>>,
+) -> Result<(), Error> {
+    let mut kept = Punctuated::new();
+    for item in std::mem::take(items) {
+        if enabled(attrs(&item), target, features)? {
+            kept.push(item);
+        }
+    }
+    *items = kept;
+    Ok(())
+}
+
+fn strip_fields(
+    fields: &mut Fields,
+    target: &Target,
+    features: Option<&HashSet<String>>,
+) -> Result<(), Error> {
+    match fields {
+        Fields::Named(x) => retain(&mut x.named, |y| &y.attrs, target, features),
+        Fields::Unnamed(x) => retain(&mut x.unnamed, |y| &y.attrs, target, features),
+        Fields::Unit => Ok(()),
+    }
+}
+
+/// Removes the disabled fields and variants of `item`, returning whether `item` itself is enabled.
+/// The items of a `mod` are left to be checked when they're added.
+pub fn strip(
+    item: &mut Item,
+    target: &Target,
+    features: Option<&HashSet<String>>,
+) -> Result<bool, Error> {
+    let attrs = match item {
+        Item::Mod(x) => &x.attrs,
+        Item::Enum(x) => &x.attrs,
+        Item::Struct(x) => &x.attrs,
+        Item::Type(x) => &x.attrs,
+        Item::Const(x) => &x.attrs,
+        Item::Static(x) => &x.attrs,
+        Item::Use(x) => &x.attrs,
+        Item::Union(x) => &x.attrs,
+        Item::ForeignMod(x) => &x.attrs,
+        _ => return Ok(true),
+    };
+    if !enabled(attrs, target, features)? {
+        return Ok(false);
+    }
+    match item {
+        Item::Struct(x) => strip_fields(&mut x.fields, target, features)?,
+        Item::Enum(x) => {
+            retain(&mut x.variants, |y| &y.attrs, target, features)?;
+            for variant in &mut x.variants {
+                strip_fields(&mut variant.fields, target, features)?;
+            }
+        }
+        Item::Union(x) => retain(&mut x.fields.named, |y| &y.attrs, target, features)?,
+        _ => {}
+    }
+    Ok(true)
+}
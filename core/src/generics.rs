@@ -0,0 +1,191 @@
+//! Substitution of generic parameters in field types.
+
+use std::collections::HashMap;
+
+use proc_macro2::TokenStream;
+use quote::ToTokens;
+use syn::{visit_mut::VisitMut, Expr, GenericArgument, GenericParam, Generics, Ident, Type};
+
+use crate::parsed;
+
+/// The argument of a type or const parameter.
+#[derive(Clone, Debug)]
+pub enum Argument {
+    Type(Type),
+    Const(Expr),
+}
+
+impl ToTokens for Argument {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        match self {
+            Argument::Type(x) => x.to_tokens(tokens),
+            Argument::Const(x) => x.to_tokens(tokens),
+        }
+    }
+}
+
+/// Whether each parameter is a const parameter rather than a type parameter.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Kind {
+    Type,
+    Const,
+}
+
+/// The type and const parameters of an item in declaration order, with their defaults.
+pub type Params = Vec<(Ident, Kind, Option<Argument>)>;
+
+pub fn params(generics: &Generics) -> Params {
+    generics
+        .params
+        .iter()
+        .filter_map(|x| match x {
+            GenericParam::Type(y) => Some((
+                y.ident.clone(),
+                Kind::Type,
+                y.default.clone().map(Argument::Type),
+            )),
+            GenericParam::Const(y) => Some((
+                y.ident.clone(),
+                Kind::Const,
+                y.default.clone().map(Argument::Const),
+            )),
+            GenericParam::Lifetime(_) => None,
+        })
+        .collect()
+}
+
+/// Converts a generic argument as written to the argument of a parameter of `kind`. A const
+/// argument that's just a name, like `N` in `Buf<N>`, is parsed as a type.
+pub fn argument(argument: GenericArgument, kind: Kind) -> Option<Argument> {
+    match (argument, kind) {
+        (GenericArgument::Type(x), Kind::Type) => Some(Argument::Type(x)),
+        (GenericArgument::Const(x), Kind::Const) => Some(Argument::Const(x)),
+        (GenericArgument::Type(Type::Path(x)), Kind::Const) if x.qself.is_none() => {
+            Some(Argument::Const(Expr::Path(syn::ExprPath {
+                attrs: Vec::new(),
+                qself: None,
+                path: x.path,
+            })))
+        }
+        _ => None,
+    }
+}
+
+struct Substitute<'a> {
+    arguments: &'a HashMap<Ident, Argument>,
+}
+
+impl VisitMut for Substitute<'_> {
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        if let Type::Path(x) = ty {
+            if x.qself.is_none() {
+                let argument = x.path.get_ident().and_then(|y| self.arguments.get(y));
+                if let Some(Argument::Type(argument)) = argument {
+                    *ty = argument.clone();
+                    return;
+                }
+            }
+        }
+        syn::visit_mut::visit_type_mut(self, ty);
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        if let Expr::Path(x) = expr {
+            if x.qself.is_none() {
+                let argument = x.path.get_ident().and_then(|y| self.arguments.get(y));
+                if let Some(Argument::Const(argument)) = argument {
+                    *expr = argument.clone();
+                    return;
+                }
+            }
+        }
+        syn::visit_mut::visit_expr_mut(self, expr);
+    }
+}
+
+/// Replaces the type and const parameters in `ty` with their arguments.
+pub fn substitute(ty: &mut Type, arguments: &HashMap<Ident, Argument>) {
+    Substitute { arguments }.visit_type_mut(ty);
+}
+
+fn substitute_argument(argument: &mut Argument, arguments: &HashMap<Ident, Argument>) {
+    match argument {
+        Argument::Type(x) => substitute(x, arguments),
+        Argument::Const(x) => Substitute { arguments }.visit_expr_mut(x),
+    }
+}
+
+fn substitute_fields(fields: &mut parsed::Struct, arguments: &HashMap<Ident, Argument>) {
+    match fields {
+        parsed::Struct::Unit => {}
+        parsed::Struct::Tuple(x) => x.iter_mut().for_each(|y| substitute(y, arguments)),
+        parsed::Struct::Struct(x) => x.iter_mut().for_each(|(_, y)| substitute(y, arguments)),
+    }
+}
+
+pub fn substitute_item(item: &mut parsed::Item, arguments: &HashMap<Ident, Argument>) {
+    match item {
+        parsed::Item::Struct(fields, _) => substitute_fields(fields, arguments),
+        parsed::Item::Enum(variants, _) => variants
+            .iter_mut()
+            .for_each(|(_, fields, _)| substitute_fields(fields, arguments)),
+        parsed::Item::Union(fields, _) => fields
+            .iter_mut()
+            .for_each(|(_, ty)| substitute(ty, arguments)),
+        parsed::Item::TypeAlias(ty) | parsed::Item::Value(ty) => substitute(ty, arguments),
+    }
+}
+
+/// Binds `params` to `arguments`, using the defaults for any missing ones. Defaults that use
+/// earlier parameters, like `U = [T; 2]`, have them substituted. Returns the arguments in order,
+/// or the first parameter without one.
+pub fn bind(params: &Params, arguments: Vec<Argument>) -> Result<Vec<(Ident, Argument)>, &Ident> {
+    let mut bound = Vec::with_capacity(params.len());
+    let mut map = HashMap::new();
+    let mut arguments = arguments.into_iter();
+    for (ident, _, default) in params {
+        let argument = match (arguments.next(), default) {
+            (Some(x), _) => x,
+            (None, Some(x)) => {
+                let mut x = x.clone();
+                substitute_argument(&mut x, &map);
+                x
+            }
+            (None, None) => return Err(ident),
+        };
+        map.insert(ident.clone(), argument.clone());
+        bound.push((ident.clone(), argument));
+    }
+    Ok(bound)
+}
+
+/// The default arguments of `params`, if there are any and they all have one.
+pub fn defaults(params: &Params) -> Option<Vec<(Ident, Argument)>> {
+    if params.is_empty() {
+        return None;
+    }
+    bind(params, Vec::new()).ok()
+}
+
+struct Canonicalize<'a> {
+    resolve: &'a dyn Fn(&syn::TypePath) -> Option<parsed::TypePath>,
+}
+
+impl VisitMut for Canonicalize<'_> {
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        if let Type::Path(x) = ty {
+            if let Some(path) = (self.resolve)(x) {
+                let path = path.0;
+                *ty = syn::parse_quote!(crate::#path);
+                return;
+            }
+        }
+        syn::visit_mut::visit_type_mut(self, ty);
+    }
+}
+
+/// Rewrites the paths to items in `ty` to start at the crate root, using `resolve` to find them,
+/// so that it means the same thing when substituted into an item in another module.
+pub fn canonicalize(ty: &mut Type, resolve: &dyn Fn(&syn::TypePath) -> Option<parsed::TypePath>) {
+    Canonicalize { resolve }.visit_type_mut(ty);
+}
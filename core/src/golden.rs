@@ -0,0 +1,144 @@
+//! Golden files: the layouts of items saved as JSON, so that later builds can check that they
+//! haven't changed.
+//!
+//! `emit_json` prints the file's contents, and `golden = "layouts.json"` checks the items against
+//! it. Types and names are stored as Rust source, and `Layout`s as their size and alignment.
+
+use std::{alloc::Layout, fmt::Write, path::PathBuf};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use syn::{parse::Error, LitStr};
+
+use crate::layout::ItemLayout;
+
+/// The golden file for `layouts`.
+pub fn to_json(layouts: &[ItemLayout]) -> String {
+    serde_json::to_string_pretty(layouts).expect("layouts are always serializable")
+}
+
+/// Checks `layouts` against the golden file named by `file`, relative to `CARGO_MANIFEST_DIR`,
+/// erroring with every item that was added, removed or changed.
+pub fn check(file: &LitStr, layouts: &[ItemLayout]) -> Result<(), Error> {
+    let mut path = std::env::var_os("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_default();
+    path.push(file.value());
+    let text = std::fs::read_to_string(&path).map_err(|err| {
+        Error::new_spanned(
+            file,
+            format!("could not read `{}`: {}", path.display(), err),
+        )
+    })?;
+    let saved: Vec<ItemLayout> = serde_json::from_str(&text)
+        .map_err(|err| Error::new_spanned(file, format!("`{}`: {}", path.display(), err)))?;
+    let mut diffs = String::new();
+    for old in &saved {
+        match layouts.iter().find(|x| x.path == old.path) {
+            None => writeln!(diffs, "`{}` is no longer analyzed", old.path).unwrap(),
+            Some(new) if new.total != old.total => writeln!(
+                diffs,
+                "`{}` changed from size {}, align {} to size {}, align {}",
+                old.path,
+                old.total.size(),
+                old.total.align(),
+                new.total.size(),
+                new.total.align()
+            )
+            .unwrap(),
+            Some(new) if new != old => writeln!(diffs, "`{}` changed", old.path).unwrap(),
+            Some(_) => {}
+        }
+    }
+    for new in layouts {
+        if !saved.iter().any(|x| x.path == new.path) {
+            writeln!(diffs, "`{}` is not in the golden file", new.path).unwrap();
+        }
+    }
+    if diffs.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::new_spanned(
+            file,
+            format!(
+                "`{}` doesn't match; update it with `emit_json`:\n{}",
+                path.display(),
+                diffs.trim_end()
+            ),
+        ))
+    }
+}
+
+/// Stores a `Layout` as `{ "size": _, "align": _ }`.
+pub mod std_layout {
+    use super::*;
+
+    #[derive(Deserialize, Serialize)]
+    struct SizeAlign {
+        size: usize,
+        align: usize,
+    }
+
+    pub fn serialize<S: Serializer>(layout: &Layout, serializer: S) -> Result<S::Ok, S::Error> {
+        SizeAlign {
+            size: layout.size(),
+            align: layout.align(),
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Layout, D::Error> {
+        let SizeAlign { size, align } = SizeAlign::deserialize(deserializer)?;
+        Layout::from_size_align(size, align).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Stores syntax like a type or an identifier as the Rust source for it.
+pub mod tokens {
+    use quote::ToTokens;
+    use syn::parse::Parse;
+
+    use super::*;
+
+    pub fn serialize<T: ToTokens, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&crate::report::tokens_to_string(value))
+    }
+
+    pub fn deserialize<'de, T: Parse, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        let source = String::deserialize(deserializer)?;
+        syn::parse_str(&source).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Stores `ItemLayout::natural_align`, whose reason is one of a few `repr`s.
+pub mod natural_align {
+    use super::*;
+
+    const REASONS: [&str; 3] = ["repr(simd)", "repr(packed)", "repr(align)"];
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<(usize, &'static str)>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        value.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<(usize, &'static str)>, D::Error> {
+        match Option::<(usize, String)>::deserialize(deserializer)? {
+            Some((align, reason)) => match REASONS.iter().find(|&&x| x == reason) {
+                Some(&x) => Ok(Some((align, x))),
+                None => Err(serde::de::Error::custom(format!(
+                    "unknown alignment reason `{}`",
+                    reason
+                ))),
+            },
+            None => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,173 @@
+//! Re-analyzing source files as they change, for the `pahole` command's `--watch` mode. The macro
+//! itself only ever sees one item.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use syn::{parse::Error, parse_quote, Item};
+
+use crate::{layout, parsed, Data};
+
+/// Keeps the items of previously analyzed files, so that only the items of changed files and
+/// the items depending on them have to be laid out again.
+pub struct Analyzer {
+    data: Data,
+    /// The module each analyzed file was added as.
+    files: HashMap<PathBuf, parsed::TypePath>,
+}
+
+impl Default for Analyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Analyzer {
+    pub fn new() -> Self {
+        Self {
+            data: Data::new(),
+            files: HashMap::new(),
+        }
+    }
+
+    /// Sets the target whose layouts are computed. Call it before analyzing any files.
+    pub fn set_target(&mut self, target: layout::Target) {
+        self.data.set_target(target);
+    }
+
+    /// Sets the features `#[cfg(feature = "...")]` is evaluated with. Call it before analyzing any
+    /// files.
+    pub fn set_features(&mut self, features: HashSet<String>) {
+        self.data.set_features(features);
+    }
+
+    /// Re-reads `changed`, adding each file as a module named after it, e.g. `a.rs` as `a`, and
+    /// lays out the items that are new, changed, or depend on a changed item, in the order they
+    /// were added. Every file is parsed before any is replaced, so that a file that doesn't parse
+    /// leaves the previous analysis in place.
+    pub fn analyze_incremental(
+        &mut self,
+        changed: &[PathBuf],
+    ) -> Result<Vec<layout::ItemLayout>, Error> {
+        let mut parsed_files: Vec<(&PathBuf, syn::Ident, Vec<Item>)> = Vec::new();
+        for file in changed {
+            let (ident, items) = parse_file(file)?;
+            let module = parsed::TypePath::from_ident(ident.clone());
+            let other = self
+                .files
+                .iter()
+                .find(|(x, y)| *x != file && **y == module)
+                .map(|(x, _)| x)
+                .or_else(|| {
+                    parsed_files
+                        .iter()
+                        .find(|(x, y, _)| *x != file && *y == ident)
+                        .map(|(x, _, _)| *x)
+                });
+            if let Some(other) = other {
+                return Err(Error::new(
+                    proc_macro2::Span::call_site(),
+                    format!(
+                        "`{}` and `{}` would both be the module `{}`",
+                        other.display(),
+                        file.display(),
+                        ident
+                    ),
+                ));
+            }
+            parsed_files.push((file, ident, items));
+        }
+
+        let mut stale = HashSet::new();
+        let mut result = Ok(());
+        for (file, ident, items) in parsed_files {
+            let module = parsed::TypePath::from_ident(ident.clone());
+            if self.files.insert(file.clone(), module.clone()).is_some() {
+                stale.extend(self.data.remove_module(&module));
+            }
+            let before = self.data.item_order.len();
+            let added = self.data.add_item(
+                parsed::TypePath::new(),
+                Item::Mod(parse_quote!(mod #ident { #(#items)* })),
+            );
+            stale.extend(self.data.item_order[before..].iter().cloned());
+            // The other files are still replaced, so that they aren't missed until saved again.
+            if result.is_ok() {
+                result = added;
+            }
+        }
+        // The items depending on the replaced files are stale even if one of them failed.
+        let stale = self.data.invalidate(stale);
+        result?;
+        self.data.resolve_where(|path| stale.contains(path))
+    }
+}
+
+fn parse_file(file: &Path) -> Result<(syn::Ident, Vec<Item>), Error> {
+    let error = |message: String| Error::new(proc_macro2::Span::call_site(), message);
+    let ident = file
+        .file_stem()
+        .and_then(|x| x.to_str())
+        .and_then(|x| syn::parse_str(x).ok())
+        .ok_or_else(|| error(format!("`{}` is not named like a module", file.display())))?;
+    let source = std::fs::read_to_string(file)
+        .map_err(|e| error(format!("could not read `{}`: {}", file.display(), e)))?;
+    Ok((ident, syn::parse_file(&source)?.items))
+}
+
+/// Whether `path` is `module` or inside it.
+fn is_within(path: &parsed::TypePath, module: &parsed::TypePath) -> bool {
+    let module = &module.0.path.segments;
+    let path = &path.0.path.segments;
+    module.len() <= path.len() && module.iter().zip(path).all(|(x, y)| x.ident == y.ident)
+}
+
+impl Data {
+    /// Removes the items and `use`s added inside `module`, returning the paths of the removed
+    /// items, including instantiations of its generic items.
+    fn remove_module(&mut self, module: &parsed::TypePath) -> Vec<parsed::TypePath> {
+        let removed = self
+            .unprocessed_items
+            .keys()
+            .filter(|x| is_within(x, module))
+            .cloned()
+            .collect();
+        self.item_order.retain(|x| !is_within(x, module));
+        self.unprocessed_items.retain(|x, _| !is_within(x, module));
+        self.imports.retain(|x, _| !is_within(x, module));
+        self.struct_definitions.retain(|x, _| !is_within(x, module));
+        self.default_instantiations
+            .retain(|x, _| !is_within(x, module));
+        self.opaque_types.retain(|x| !is_within(x, module));
+        self.generic_templates.retain(|x, _| !is_within(x, module));
+        removed
+    }
+
+    /// Forgets the computed layouts of `paths` and of the items depending on them, directly or
+    /// indirectly, returning all of those paths.
+    fn invalidate(&mut self, mut paths: HashSet<parsed::TypePath>) -> HashSet<parsed::TypePath> {
+        loop {
+            let dependents: Vec<_> = self
+                .dependencies
+                .iter()
+                .filter(|(x, y)| !paths.contains(*x) && y.iter().any(|z| paths.contains(z)))
+                .map(|(x, _)| x.clone())
+                .collect();
+            if dependents.is_empty() {
+                break;
+            }
+            paths.extend(dependents);
+        }
+        for path in &paths {
+            self.processed_items.remove(path);
+            self.niches.remove(path);
+            self.ffi_unsafe.remove(path);
+            self.zero_invalid.remove(path);
+            self.drop_glue.remove(path);
+            self.dependencies.remove(path);
+        }
+        paths
+    }
+}
@@ -0,0 +1,542 @@
+use std::alloc::Layout;
+
+use crate::parsed;
+
+/// The properties of the compilation target that layouts depend on.
+///
+/// Only the pointer width is modelled; other primitives use the host's layout.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Target {
+    /// The size of a pointer in bits, as in `cfg(target_pointer_width)`.
+    pub pointer_width: usize,
+}
+
+impl Target {
+    /// The pointer widths rustc supports, in bits, from narrowest to widest.
+    pub const POINTER_WIDTHS: [usize; 3] = [16, 32, 64];
+
+    pub fn host() -> Self {
+        Self {
+            pointer_width: usize::BITS as usize,
+        }
+    }
+
+    /// The layout of `usize`, `isize` and thin pointers.
+    pub fn pointer(&self) -> Layout {
+        let size = self.pointer_width / 8;
+        Layout::from_size_align(size, size).unwrap()
+    }
+
+    /// The number of invalid values of the capacity of a `String` or `Vec`, which can't exceed
+    /// `isize::MAX`, and so of the buffer itself.
+    pub fn capacity_niche(&self) -> u128 {
+        1 << (self.pointer_width - 1)
+    }
+
+    /// The layout of pointers to slices, `str`s and trait objects.
+    pub fn fat_pointer(&self) -> Layout {
+        let pointer = self.pointer();
+        Layout::from_size_align(2 * pointer.size(), pointer.align()).unwrap()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct FieldLayout {
+    pub name: String,
+    #[cfg_attr(feature = "serde", serde(with = "crate::golden::tokens"))]
+    pub ty: syn::Type,
+    pub offset: usize,
+    #[cfg_attr(feature = "serde", serde(with = "crate::golden::std_layout"))]
+    pub layout: Layout,
+    /// The padding inside the field's value when it's a tuple or array, rather than a named type
+    /// with a report of its own. Arrays of named types count the padding of every element.
+    pub internal_padding: usize,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct Hole {
+    pub offset: usize,
+    pub size: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct StructLayout {
+    pub fields: Vec<FieldLayout>,
+    pub holes: Vec<Hole>,
+    pub padding: usize,
+    #[cfg_attr(feature = "serde", serde(with = "crate::golden::std_layout"))]
+    pub total: Layout,
+    /// Whether the last field is unsized, so that `total` only covers the sized prefix.
+    pub unsized_tail: bool,
+    /// Whether the last field is a `[T; 0]` in a `repr(C)` struct, standing in for a C flexible
+    /// array member.
+    pub flexible_array: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct VariantLayout {
+    #[cfg_attr(feature = "serde", serde(with = "crate::golden::tokens"))]
+    pub name: syn::Ident,
+    pub discriminant: i128,
+    pub payload: StructLayout,
+    /// Whether the fields were reordered so that small ones share the tag's alignment unit,
+    /// which declaration order would have left as padding.
+    pub packed_tag: bool,
+}
+
+/// The integer type an enum's discriminant is stored as.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct Tag {
+    #[cfg_attr(feature = "serde", serde(with = "crate::golden::std_layout"))]
+    pub layout: Layout,
+    pub signed: bool,
+}
+
+impl Tag {
+    /// The name of the integer type, e.g. `u8`.
+    pub fn name(&self) -> String {
+        let sign = if self.signed { 'i' } else { 'u' };
+        format!("{}{}", sign, 8 * self.layout.size())
+    }
+
+    /// The number of values of the tag not used by any of `variant_count` variants.
+    pub fn niche(&self, variant_count: usize) -> u128 {
+        let values_minus_one = u128::MAX >> (128 - 8 * self.layout.size());
+        values_minus_one - (variant_count as u128).saturating_sub(1)
+    }
+}
+
+/// The tag named by an integer `repr` like `u8`, or `None` if `name` isn't an integer type.
+pub fn int_tag(name: &syn::Ident, pointer_width: usize) -> Option<Tag> {
+    let name = name.to_string();
+    let signed = match name.as_bytes().first() {
+        Some(b'i') => true,
+        Some(b'u') => false,
+        _ => return None,
+    };
+    let bits = match &name[1..] {
+        "size" => pointer_width,
+        x => x
+            .parse()
+            .ok()
+            .filter(|x| matches!(x, 8 | 16 | 32 | 64 | 128))?,
+    };
+    let layout = Layout::from_size_align(bits / 8, bits / 8).unwrap();
+    Some(Tag { layout, signed })
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub enum Discriminant {
+    /// Enums with at most one variant don't need to store which variant they are.
+    None,
+    Tag(Tag),
+    /// The discriminant is stored in invalid values of a field of the only variant with data.
+    Niche {
+        #[cfg_attr(feature = "serde", serde(with = "crate::golden::tokens"))]
+        variant: syn::Ident,
+        field: String,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub enum Shape {
+    Struct(StructLayout),
+    Enum {
+        discriminant: Discriminant,
+        variants: Vec<VariantLayout>,
+    },
+    Union(Vec<FieldLayout>),
+    TypeAlias(#[cfg_attr(feature = "serde", serde(with = "crate::golden::tokens"))] syn::Type),
+    /// The declared type of a `const` or `static`.
+    Value(#[cfg_attr(feature = "serde", serde(with = "crate::golden::tokens"))] syn::Type),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct ItemLayout {
+    pub path: parsed::TypePath,
+    #[cfg_attr(feature = "serde", serde(with = "crate::golden::std_layout"))]
+    pub total: Layout,
+    pub shape: Shape,
+    /// The number of invalid values of the item, which enclosing enums can use.
+    pub niche: u128,
+    /// Why the item can't be used in FFI, or `None` if it's FFI-safe.
+    pub ffi_unsafe: Option<String>,
+    /// Why all-zero bytes aren't a valid value of the item, or `None` if `mem::zeroed` is sound
+    /// for it.
+    pub zero_invalid: Option<String>,
+    /// Why dropping the item runs code, or `None` if pahole knows of no reason it would.
+    pub drop_glue: Option<String>,
+    /// The alignment the item's fields call for and the `repr` changing it, e.g.
+    /// `(8, "repr(packed)")`, if its `repr` gives it a different alignment.
+    #[cfg_attr(feature = "serde", serde(with = "crate::golden::natural_align"))]
+    pub natural_align: Option<(usize, &'static str)>,
+}
+
+impl ItemLayout {
+    /// The bytes of the item not occupied by any field or tag. For enums and unions, that's what's
+    /// left over by the variant or field using the most space.
+    pub fn padding(&self) -> usize {
+        let used = |x: &StructLayout| x.total.size() - x.padding - x.hole_bytes();
+        match &self.shape {
+            Shape::Struct(x) => x.hole_bytes() + x.padding,
+            Shape::Enum { variants, .. } => {
+                self.total.size() - variants.iter().map(|x| used(&x.payload)).max().unwrap_or(0)
+            }
+            Shape::Union(fields) => {
+                self.total.size() - fields.iter().map(|x| x.layout.size()).max().unwrap_or(0)
+            }
+            Shape::TypeAlias(_) | Shape::Value(_) => 0,
+        }
+    }
+}
+
+/// Whether `a` and `b` place their bytes identically: the same size and alignment, with fields of
+/// the same sizes and alignments at the same offsets, whatever their names, types, and
+/// declaration order.
+pub fn same_layout(a: &ItemLayout, b: &ItemLayout) -> bool {
+    a.total == b.total
+        && match (signature(&a.shape), signature(&b.shape)) {
+            (Some(x), Some(y)) => x == y,
+            _ => false,
+        }
+}
+
+type StructSignature = (Vec<(usize, usize, usize)>, Vec<Hole>, usize);
+
+/// The tag, if any, and the fields and holes of each variant, with the fields in offset order.
+fn signature(shape: &Shape) -> Option<(Option<Layout>, Vec<StructSignature>)> {
+    let fields = |fields: &[FieldLayout]| {
+        let mut fields: Vec<_> = fields
+            .iter()
+            .map(|x| (x.offset, x.layout.size(), x.layout.align()))
+            .collect();
+        fields.sort_unstable();
+        fields
+    };
+    let payload = |x: &StructLayout| (fields(&x.fields), x.holes.clone(), x.padding);
+    match shape {
+        Shape::Struct(x) => Some((None, vec![payload(x)])),
+        Shape::Enum {
+            discriminant,
+            variants,
+        } => {
+            let tag = match discriminant {
+                Discriminant::Tag(x) => Some(x.layout),
+                Discriminant::None | Discriminant::Niche { .. } => None,
+            };
+            Some((tag, variants.iter().map(|x| payload(&x.payload)).collect()))
+        }
+        Shape::Union(x) => Some((None, vec![(fields(x), Vec::new(), 0)])),
+        Shape::TypeAlias(_) | Shape::Value(_) => None,
+    }
+}
+
+/// Whether rustc chooses the order of the fields of a struct with `repr`, as it does unless the
+/// `repr` is `C` or `simd`.
+pub fn reorders_fields(repr: &parsed::Repr) -> bool {
+    !repr.c && !repr.simd
+}
+
+impl StructLayout {
+    /// Like `with_repr`, but in the order rustc currently picks for a struct it may reorder:
+    /// zero-sized fields first, then the rest by descending alignment, the ones with more invalid
+    /// values (`niches`) first. A `fixed_tail` field, like an unsized one, stays last.
+    pub fn optimized(
+        fields: Vec<(String, syn::Type, Layout)>,
+        niches: &[u128],
+        repr: &parsed::Repr,
+        fixed_tail: bool,
+    ) -> Self {
+        let sized = fields.len() - fixed_tail as usize;
+        let max_align = fields[..sized]
+            .iter()
+            .map(|x| x.2.align())
+            .max()
+            .unwrap_or(1);
+        let has_niche = niches[..sized].iter().any(|&x| x != 0);
+        let group = |layout: Layout| match repr.packed {
+            Some(pack) => layout.align().min(pack).trailing_zeros(),
+            None => {
+                let size_as_align = layout.align().max(layout.size()).trailing_zeros();
+                if has_niche {
+                    size_as_align.min(max_align.trailing_zeros())
+                } else {
+                    size_as_align
+                }
+            }
+        };
+        let mut order: Vec<usize> = (0..sized).collect();
+        order.sort_by_key(|&i| {
+            let layout = fields[i].2;
+            let is_1zst = layout.size() == 0 && layout.align() == 1;
+            (
+                !is_1zst,
+                std::cmp::Reverse(group(layout)),
+                std::cmp::Reverse(niches[i]),
+            )
+        });
+        order.extend(sized..fields.len());
+        let mut fields: Vec<_> = fields.into_iter().map(Some).collect();
+        let ordered = order.iter().map(|&i| fields[i].take().unwrap()).collect();
+        Self::with_repr(Layout::new::<()>(), ordered, repr)
+    }
+
+    /// Lays out `fields` in declaration order, starting after `prefix` (e.g. an enum's tag).
+    pub fn sequential(prefix: Layout, fields: Vec<(String, syn::Type, Layout)>) -> Self {
+        Self::with_repr(prefix, fields, &parsed::Repr::default())
+    }
+
+    /// Like `sequential`, but applying `repr(packed)` and `repr(align)`.
+    ///
+    /// Zero-sized fields take no space but are still aligned, so they can push later fields back
+    /// and raise the struct's alignment. This matches rustc; C has no zero-sized types.
+    pub fn with_repr(
+        prefix: Layout,
+        fields: Vec<(String, syn::Type, Layout)>,
+        repr: &parsed::Repr,
+    ) -> Self {
+        let mut current = prefix;
+        let mut holes = Vec::new();
+        let mut laid_out = Vec::with_capacity(fields.len());
+        for (name, ty, layout) in fields {
+            let end = current.size();
+            let placed = packed(layout, repr);
+            let (next, offset) = current.extend(placed).expect("struct layout overflowed");
+            if offset > end {
+                holes.push(Hole {
+                    offset: end,
+                    size: offset - end,
+                });
+            }
+            laid_out.push(FieldLayout {
+                name,
+                ty,
+                offset,
+                layout,
+                internal_padding: 0,
+            });
+            current = next;
+        }
+        let end = current.size();
+        let total = over_aligned(current, repr).pad_to_align();
+        Self {
+            fields: laid_out,
+            holes,
+            padding: total.size() - end,
+            total,
+            unsized_tail: false,
+            flexible_array: false,
+        }
+    }
+
+    /// Finds a field order with less padding, returning the new order (as indices into `fields`)
+    /// and the resulting layout, if it's smaller.
+    pub fn reordered(&self) -> Option<(Vec<usize>, StructLayout)> {
+        // An unsized field or flexible array member has to stay last.
+        let sized = self.fields.len() - (self.unsized_tail || self.flexible_array) as usize;
+        let mut order: Vec<usize> = (0..sized).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse(self.fields[i].layout.align()));
+        order.extend(sized..self.fields.len());
+        let fields = order
+            .iter()
+            .map(|&i| {
+                let field = &self.fields[i];
+                (field.name.clone(), field.ty.clone(), field.layout)
+            })
+            .collect();
+        let mut layout = Self::sequential(Layout::new::<()>(), fields);
+        layout.unsized_tail = self.unsized_tail;
+        layout.flexible_array = self.flexible_array;
+        if layout.total.size() < self.total.size() {
+            Some((order, layout))
+        } else {
+            None
+        }
+    }
+
+    pub fn hole_bytes(&self) -> usize {
+        self.holes.iter().map(|x| x.size).sum()
+    }
+
+    /// Finds fields smaller than the struct's alignment that are followed by holes, and lays the
+    /// struct out with all of its small fields moved after the others, unless that makes it larger.
+    pub fn grouped_small_fields(&self) -> Option<(Vec<usize>, StructLayout)> {
+        if self.unsized_tail {
+            return None;
+        }
+        let align = self.total.align();
+        let is_small = |x: &FieldLayout| x.layout.size() < align;
+        let scattered: Vec<usize> = (0..self.fields.len())
+            .filter(|&i| {
+                let field = &self.fields[i];
+                is_small(field)
+                    && self
+                        .holes
+                        .iter()
+                        .any(|x| x.offset == field.offset + field.layout.size())
+            })
+            .collect();
+        if scattered.is_empty() {
+            return None;
+        }
+        let (mut small, large): (Vec<usize>, Vec<usize>) =
+            (0..self.fields.len()).partition(|&i| is_small(&self.fields[i]));
+        small.sort_by_key(|&i| std::cmp::Reverse(self.fields[i].layout.align()));
+        let fields = large
+            .into_iter()
+            .chain(small)
+            .map(|i| {
+                let field = &self.fields[i];
+                (field.name.clone(), field.ty.clone(), field.layout)
+            })
+            .collect();
+        let layout = Self::sequential(Layout::new::<()>(), fields);
+        if layout.hole_bytes() < self.hole_bytes() && layout.total.size() <= self.total.size() {
+            Some((scattered, layout))
+        } else {
+            None
+        }
+    }
+
+    /// Finds later fields that fit in a hole, returning for each the field's index, the index of
+    /// the field before the hole, and the layout with the field moved there, if that's smaller.
+    /// Each hole and field is used at most once.
+    pub fn hole_fillings(&self) -> Vec<(usize, usize, StructLayout)> {
+        let sized = self.fields.len() - self.unsized_tail as usize;
+        let mut moved = Vec::new();
+        let mut fillings = Vec::new();
+        for hole in &self.holes {
+            let before = match self.fields[..sized]
+                .iter()
+                .position(|x| x.offset + x.layout.size() == hole.offset)
+            {
+                Some(x) => x,
+                None => continue,
+            };
+            for i in before + 1..sized {
+                let field = &self.fields[i];
+                let size = field.layout.size();
+                let align = field.layout.align();
+                let start = hole.offset.div_ceil(align) * align;
+                if size == 0 || moved.contains(&i) || start + size > hole.offset + hole.size {
+                    continue;
+                }
+                let order = (0..=before)
+                    .chain(std::iter::once(i))
+                    .chain((before + 1..self.fields.len()).filter(|&x| x != i));
+                let fields = order
+                    .map(|x| {
+                        let field = &self.fields[x];
+                        (field.name.clone(), field.ty.clone(), field.layout)
+                    })
+                    .collect();
+                let mut layout = Self::sequential(Layout::new::<()>(), fields);
+                layout.unsized_tail = self.unsized_tail;
+                if layout.total.size() < self.total.size() {
+                    moved.push(i);
+                    fillings.push((i, before, layout));
+                    break;
+                }
+            }
+        }
+        fillings
+    }
+}
+
+/// The tag used by a default-repr enum with variants numbered `discriminants`: the smallest
+/// integer type that fits all of them, signed only if one is negative.
+pub fn enum_tag(discriminants: &[i128]) -> Option<Tag> {
+    if discriminants.len() <= 1 {
+        return None;
+    }
+    let min = discriminants.iter().copied().min().unwrap();
+    let max = discriminants.iter().copied().max().unwrap();
+    let signed = min < 0;
+    let layout = [
+        Layout::new::<u8>(),
+        Layout::new::<u16>(),
+        Layout::new::<u32>(),
+        Layout::new::<u64>(),
+    ]
+    .iter()
+    .copied()
+    .find(|x| {
+        let bits = 8 * x.size() as u32;
+        if signed {
+            min >= -(1 << (bits - 1)) && max < 1 << (bits - 1)
+        } else {
+            max < 1 << bits
+        }
+    })
+    // Discriminants are `isize`s, so rustc rejects any that don't fit in 64 bits.
+    .unwrap_or_else(Layout::new::<u64>);
+    Some(Tag { layout, signed })
+}
+
+pub fn enum_total(tag: Option<Tag>, variants: &[VariantLayout]) -> Layout {
+    let mut size = tag.map_or(0, |x| x.layout.size());
+    let mut align = tag.map_or(1, |x| x.layout.align());
+    for variant in variants {
+        size = size.max(variant.payload.total.size());
+        align = align.max(variant.payload.total.align());
+    }
+    Layout::from_size_align(size, align)
+        .expect("enum layout overflowed")
+        .pad_to_align()
+}
+
+/// The layout a field is placed with under `repr(packed(N))`.
+fn packed(layout: Layout, repr: &parsed::Repr) -> Layout {
+    match repr.packed {
+        Some(pack) if pack < layout.align() => {
+            Layout::from_size_align(layout.size(), pack).unwrap()
+        }
+        _ => layout,
+    }
+}
+
+fn over_aligned(layout: Layout, repr: &parsed::Repr) -> Layout {
+    // Vectors are aligned to their size, rounded up to a power of two.
+    let layout = if repr.simd {
+        let align = layout.size().next_power_of_two();
+        layout.align_to(align).expect("alignment overflowed")
+    } else {
+        layout
+    };
+    match repr.align {
+        Some(align) => layout.align_to(align).expect("alignment overflowed"),
+        None => layout,
+    }
+}
+
+pub fn union_fields(
+    fields: Vec<(String, syn::Type, Layout)>,
+    repr: &parsed::Repr,
+) -> (Vec<FieldLayout>, Layout) {
+    let mut size = 0;
+    let mut align = 1;
+    let laid_out = fields
+        .into_iter()
+        .map(|(name, ty, layout)| {
+            size = size.max(layout.size());
+            align = align.max(packed(layout, repr).align());
+            FieldLayout {
+                name,
+                ty,
+                offset: 0,
+                layout,
+                internal_padding: 0,
+            }
+        })
+        .collect();
+    let total = Layout::from_size_align(size, align).expect("union layout overflowed");
+    (laid_out, over_aligned(total, repr).pad_to_align())
+}
@@ -0,0 +1,6186 @@
+//! The analysis behind the `pahole` attribute of `pahole-macro` and the `pahole` command. A
+//! proc-macro crate can only export macros, so they share it through this crate.
+
+use std::{
+    alloc::Layout,
+    collections::{HashMap, HashSet},
+    fmt::Write,
+};
+use syn::{
+    parse::Error, parse_quote, AttributeArgs, Expr, ExprLit, Fields, Item, Lit, LitStr, Type,
+};
+
+mod cfg;
+mod generics;
+#[cfg(feature = "serde")]
+pub mod golden;
+pub mod incremental;
+pub mod layout;
+pub mod options;
+pub mod report;
+mod sizes;
+mod std_layouts;
+mod what_if;
+
+pub mod parsed {
+    #[derive(Clone, Debug)]
+    #[allow(clippy::enum_variant_names)]
+    pub enum Struct {
+        Unit,
+        Tuple(Vec<syn::Type>),
+        Struct(Vec<(syn::Ident, syn::Type)>),
+    }
+
+    /// The layout-affecting parts of a `#[repr(...)]` attribute.
+    #[derive(Clone, Debug, Default)]
+    pub struct Repr {
+        pub c: bool,
+        pub transparent: bool,
+        pub packed: Option<usize>,
+        pub align: Option<usize>,
+        pub simd: bool,
+        /// The integer type of an enum's tag, e.g. `u8`.
+        pub int: Option<syn::Ident>,
+    }
+
+    #[derive(Clone, Debug)]
+    #[allow(clippy::large_enum_variant)]
+    pub enum Item {
+        Struct(Struct, Repr),
+        /// The variants, with their discriminants.
+        Enum(Vec<(syn::Ident, Struct, i128)>, Repr),
+        Union(Vec<(syn::Ident, syn::Type)>, Repr),
+        TypeAlias(syn::Type),
+        /// A `const` or `static`, whose declared type is reported.
+        Value(syn::Type),
+    }
+
+    #[derive(Clone, Debug, Eq, PartialEq, Hash)]
+    #[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+    pub struct TypePath(
+        #[cfg_attr(feature = "serde", serde(with = "crate::golden::tokens"))] pub syn::TypePath,
+    );
+
+    impl Default for TypePath {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl TypePath {
+        pub fn new() -> Self {
+            Self(syn::TypePath {
+                qself: None,
+                path: syn::Path {
+                    leading_colon: None,
+                    segments: syn::punctuated::Punctuated::new(),
+                },
+            })
+        }
+
+        pub fn from_ident(ident: syn::Ident) -> Self {
+            let mut path = Self::new();
+            path.push(ident.into());
+            path
+        }
+
+        pub fn is_absolute(&self) -> bool {
+            if self.0.path.leading_colon.is_none() {
+                assert!(self.0.qself.is_none());
+            }
+            self.0.path.leading_colon.is_some()
+        }
+
+        pub fn concat(&self, other: TypePath) -> Self {
+            if other.is_absolute() || self.0.path.segments.is_empty() {
+                other
+            } else {
+                let mut segments = self.0.path.segments.clone();
+                segments.push_punct(<syn::Token![::]>::default());
+                segments.extend(other.0.path.segments);
+                TypePath(syn::TypePath {
+                    qself: self.0.qself.clone(),
+                    path: syn::Path {
+                        leading_colon: self.0.path.leading_colon,
+                        segments,
+                    },
+                })
+            }
+        }
+
+        pub fn push(&mut self, item: syn::PathSegment) {
+            self.0.path.segments.push(item)
+        }
+
+        pub fn parent(&self) -> Option<Self> {
+            let mut parent = self.clone();
+            parent.0.path.segments.pop()?;
+            // Popping the last segment leaves the preceding `::` behind.
+            let segments = parent.0.path.segments.into_pairs().map(|x| x.into_value());
+            parent.0.path.segments = segments.collect();
+            Some(parent)
+        }
+    }
+
+    /// Renders the path as it would be written, e.g. `a::b::C<u8>`.
+    impl std::fmt::Display for TypePath {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str(&crate::report::tokens_to_string(&self.0))
+        }
+    }
+}
+
+macro_rules! impl_add_builtins {
+    ($self:ident; $($type:ty)*) => {
+        $($self.processed_items.insert(parsed::TypePath(parse_quote!($type)), Layout::new::<$type>());)*
+    }
+}
+
+macro_rules! impl_add_niches {
+    ($self:ident; $($type:ty => $niche:expr),* $(,)?) => {
+        $($self.niches.insert(parsed::TypePath(parse_quote!($type)), $niche);)*
+    }
+}
+
+/// Splits a field of a struct or union with named fields into its name and type. Only input
+/// built by hand rather than parsed can lack the name.
+fn named_field(field: syn::Field) -> Result<(syn::Ident, Type), Error> {
+    match field.ident {
+        Some(ident) => Ok((ident, field.ty)),
+        None => Err(Error::new_spanned(field, "expected a named field")),
+    }
+}
+
+fn parse_struct_fields(fields: Fields) -> Result<parsed::Struct, Error> {
+    Ok(match fields {
+        Fields::Named(x) => parsed::Struct::Struct(
+            x.named
+                .into_iter()
+                .map(named_field)
+                .collect::<Result<_, _>>()?,
+        ),
+        #[rustfmt::skip]
+        Fields::Unnamed(x) => parsed::Struct::Tuple(
+            x.unnamed
+                .into_iter()
+                .map(|y| y.ty)
+                .collect()
+        ),
+        Fields::Unit => parsed::Struct::Unit,
+    })
+}
+
+fn parse_repr_int(lit: &syn::NestedMeta) -> Result<usize, Error> {
+    match lit {
+        syn::NestedMeta::Lit(Lit::Int(x)) => {
+            let value: usize = x.base10_parse()?;
+            if value.is_power_of_two() {
+                Ok(value)
+            } else {
+                Err(Error::new_spanned(x, "expected a power of two"))
+            }
+        }
+        // rustc only accepts literals here, so e.g. const generic parameters can't be bound later.
+        syn::NestedMeta::Meta(syn::Meta::Path(x)) => Err(Error::new_spanned(
+            x,
+            "`repr` arguments must be integer literals, not constants or const generic parameters",
+        )),
+        _ => Err(Error::new_spanned(lit, "expected an integer")),
+    }
+}
+
+fn parse_repr(attrs: &[syn::Attribute]) -> Result<parsed::Repr, Error> {
+    let mut repr = parsed::Repr::default();
+    for attr in attrs.iter().filter(|x| x.path.is_ident("repr")) {
+        let list = match attr.parse_meta()? {
+            syn::Meta::List(x) => x,
+            meta => return Err(Error::new_spanned(meta, "expected `repr(...)`")),
+        };
+        for nested in list.nested {
+            match &nested {
+                syn::NestedMeta::Meta(syn::Meta::Path(x)) if x.is_ident("C") => repr.c = true,
+                syn::NestedMeta::Meta(syn::Meta::Path(x)) if x.is_ident("Rust") => {}
+                syn::NestedMeta::Meta(syn::Meta::Path(x)) if x.is_ident("transparent") => {
+                    repr.transparent = true
+                }
+                syn::NestedMeta::Meta(syn::Meta::Path(x)) if x.is_ident("packed") => {
+                    repr.packed = Some(1)
+                }
+                syn::NestedMeta::Meta(syn::Meta::Path(x)) if x.is_ident("simd") => repr.simd = true,
+                syn::NestedMeta::Meta(syn::Meta::Path(x))
+                    if x.get_ident()
+                        .is_some_and(|y| layout::int_tag(y, 64).is_some()) =>
+                {
+                    repr.int = x.get_ident().cloned()
+                }
+                syn::NestedMeta::Meta(syn::Meta::List(x))
+                    if x.nested.len() == 1
+                        && (x.path.is_ident("packed") || x.path.is_ident("align")) =>
+                {
+                    let value = parse_repr_int(&x.nested[0])?;
+                    if x.path.is_ident("packed") {
+                        repr.packed = Some(value);
+                    } else {
+                        repr.align = Some(repr.align.map_or(value, |x| x.max(value)));
+                    }
+                }
+                _ => {
+                    return Err(Error::new_spanned(
+                        nested,
+                        "pahole does not currently support this `repr`",
+                    ))
+                }
+            }
+        }
+    }
+    Ok(repr)
+}
+
+#[derive(Debug)]
+struct Data {
+    unprocessed_items: HashMap<parsed::TypePath, parsed::Item>,
+    processed_items: HashMap<parsed::TypePath, Layout>,
+    /// The number of invalid values of each processed type, which enclosing enums can use to
+    /// store their discriminant. Types without an entry have no niche.
+    niches: HashMap<parsed::TypePath, u128>,
+    /// Why each processed type that can't be used in FFI isn't FFI-safe.
+    ffi_unsafe: HashMap<parsed::TypePath, String>,
+    /// Why all-zero bytes aren't a valid value of each processed type that has such a reason.
+    zero_invalid: HashMap<parsed::TypePath, String>,
+    /// Why each processed type that has drop glue has it.
+    drop_glue: HashMap<parsed::TypePath, String>,
+    item_order: Vec<parsed::TypePath>,
+    /// The items brought into scope by `use`, mapping the imported name to the module containing
+    /// the `use` and the path it imports.
+    imports: HashMap<parsed::TypePath, (parsed::TypePath, syn::TypePath)>,
+    /// The numbers registered with `add_type_number`.
+    type_numbers: HashMap<parsed::TypePath, usize>,
+    /// Layouts registered with `add_layout`, which survive `clear_processed`.
+    custom_layouts: HashMap<parsed::TypePath, Layout>,
+    /// Niches registered with `register_niche`, which take precedence over computed ones.
+    custom_niches: HashMap<parsed::TypePath, u128>,
+    target: layout::Target,
+    /// The enabled features that `#[cfg(feature = "...")]` checks for, which are unknown if this
+    /// is `None`.
+    features: Option<HashSet<String>>,
+    /// The original definitions of structs, used to render suggestions.
+    struct_definitions: HashMap<parsed::TypePath, syn::ItemStruct>,
+    in_progress: HashSet<parsed::TypePath>,
+    /// The types from `std_layouts` that were used, whose layouts are version-dependent.
+    std_layouts_used: HashSet<parsed::TypePath>,
+    /// The added items each item's layout depends on, in the order they were first used.
+    dependencies: HashMap<parsed::TypePath, Vec<parsed::TypePath>>,
+    /// The generic items laid out with their default arguments, with how that instantiation is
+    /// written, e.g. `S<u32>`.
+    default_instantiations: HashMap<parsed::TypePath, String>,
+    /// Types declared in `extern` blocks, whose layout is unknown.
+    opaque_types: HashSet<parsed::TypePath>,
+    /// The type parameters and unsubstituted definitions of generic items, from which
+    /// instantiations like `Wrapper<u32>` are added as they're used.
+    generic_templates: HashMap<parsed::TypePath, (generics::Params, parsed::Item)>,
+    /// How to lay out types that can't be found, which are errors if this is `None`.
+    unknown_types: Option<options::UnknownTypes>,
+    /// The types laid out according to `unknown_types`, in the order they were first used.
+    assumed_types: Vec<String>,
+    /// Whether the primitives and standard library types with fixed layouts are known.
+    builtins: bool,
+}
+
+fn array_len(expr: &Expr) -> Result<usize, Error> {
+    match expr {
+        Expr::Lit(ExprLit {
+            lit: Lit::Int(x), ..
+        }) => x.base10_parse(),
+        _ => Err(Error::new_spanned(
+            expr,
+            "pahole can currently only handle integer literal array lengths",
+        )),
+    }
+}
+
+fn discriminant_value(expr: &Expr) -> Result<i128, Error> {
+    match expr {
+        Expr::Lit(ExprLit {
+            lit: Lit::Int(x), ..
+        }) => x.base10_parse(),
+        Expr::Unary(syn::ExprUnary {
+            op: syn::UnOp::Neg(_),
+            expr,
+            ..
+        }) => Ok(-discriminant_value(expr)?),
+        Expr::Paren(x) => discriminant_value(&x.expr),
+        _ => Err(Error::new_spanned(
+            expr,
+            "pahole can currently only handle integer literal discriminants",
+        )),
+    }
+}
+
+/// The pointee of a `Box<T>` or `NonNull<T>`, which are pointers that can't be null.
+fn non_null_pointee(path: &syn::TypePath) -> Option<&Type> {
+    generic_argument(path, "Box").or_else(|| generic_argument(path, "NonNull"))
+}
+
+/// Whether `ty` is a reference, `fn` pointer, `Box`, or `NonNull`, which `Option` stores `None`
+/// in as null.
+fn is_non_null_pointer(ty: &Type) -> bool {
+    match ty {
+        Type::Reference(_) | Type::BareFn(_) => true,
+        Type::Path(x) => non_null_pointee(x).is_some(),
+        Type::Paren(x) => is_non_null_pointer(&x.elem),
+        Type::Group(x) => is_non_null_pointer(&x.elem),
+        _ => false,
+    }
+}
+
+/// Whether `ty` names one of the `NonZero` integers.
+fn is_non_zero(ty: &Type) -> bool {
+    match ty {
+        Type::Path(x) => x
+            .path
+            .segments
+            .last()
+            .is_some_and(|y| y.ident.to_string().starts_with("NonZero")),
+        _ => false,
+    }
+}
+
+/// The single type argument of `path` if its last segment is `name<T>`, e.g. `std::boxed::Box<T>`.
+fn generic_argument<'a>(path: &'a syn::TypePath, name: &str) -> Option<&'a Type> {
+    match generic_arguments(path, name)?.as_slice() {
+        [ty] => Some(ty),
+        _ => None,
+    }
+}
+
+/// The type arguments of `path` if it names `name`, e.g. `T` and `E` in `Result<T, E>`.
+fn generic_arguments<'a>(path: &'a syn::TypePath, name: &str) -> Option<Vec<&'a Type>> {
+    let last = path.path.segments.last()?;
+    if path.qself.is_some() || last.ident != name {
+        return None;
+    }
+    match &last.arguments {
+        syn::PathArguments::AngleBracketed(x) => x
+            .args
+            .iter()
+            .map(|arg| match arg {
+                syn::GenericArgument::Type(ty) => Some(ty),
+                _ => None,
+            })
+            .collect(),
+        _ => None,
+    }
+}
+
+/// The standard library's owning pointers, which have drop glue whatever they point to.
+const OWNING: &[&str] = &["Box", "Rc", "Arc"];
+
+/// Whether `path` is a `String` or `Vec<T>`, which are a pointer, a capacity, and a length.
+fn is_heap_buffer(path: &syn::TypePath) -> bool {
+    let is_string = path.qself.is_none()
+        && path
+            .path
+            .segments
+            .last()
+            .is_some_and(|x| x.ident == "String" && x.arguments.is_empty());
+    is_string || generic_argument(path, "Vec").is_some()
+}
+
+/// The `T` and `E` of `Result<T, E>`.
+fn result_arguments(path: &syn::TypePath) -> Option<(&Type, &Type)> {
+    match generic_arguments(path, "Result")?.as_slice() {
+        &[ok, err] => Some((ok, err)),
+        _ => None,
+    }
+}
+
+/// The `B` of `Cow<'a, B>`.
+fn cow_argument(path: &syn::TypePath) -> Option<&Type> {
+    let last = path.path.segments.last()?;
+    if path.qself.is_some() || last.ident != "Cow" {
+        return None;
+    }
+    let types: Vec<&Type> = match &last.arguments {
+        syn::PathArguments::AngleBracketed(x) => x
+            .args
+            .iter()
+            .filter_map(|arg| match arg {
+                syn::GenericArgument::Type(ty) => Some(ty),
+                _ => None,
+            })
+            .collect(),
+        _ => return None,
+    };
+    match types.as_slice() {
+        [ty] => Some(ty),
+        _ => None,
+    }
+}
+
+/// The owned type of `path`, a `Cow<B>`, for the `B`s whose owned type pahole knows.
+fn cow_owned(path: &syn::TypePath, borrowed: &Type) -> Result<Type, Error> {
+    match borrowed {
+        Type::Path(x) if x.path.is_ident("str") => Ok(parse_quote!(String)),
+        Type::Slice(x) => {
+            let elem = &x.elem;
+            Ok(parse_quote!(Vec<#elem>))
+        }
+        _ => Err(Error::new_spanned(
+            path,
+            format!(
+                "the layout of `{}` depends on the owned type of `{}`, which pahole only knows \
+                 for `str` and slices",
+                report::tokens_to_string(path),
+                report::tokens_to_string(borrowed)
+            ),
+        )),
+    }
+}
+
+/// The error for the unsized type `ty` used where only sized types fit.
+fn unsized_error(ty: &impl quote::ToTokens) -> Error {
+    Error::new_spanned(
+        ty,
+        format!(
+            "`{}` is unsized and can only be used behind a pointer or as the last field of a struct",
+            report::tokens_to_string(ty)
+        ),
+    )
+}
+
+/// How many `use` imports may be chained, guarding against import cycles.
+const MAX_IMPORT_DEPTH: usize = 16;
+
+/// Splits a leading `crate`, `self`, or `super` off of `path`, returning the module to start
+/// looking in, the rest of the path, and whether enclosing modules should be searched too.
+fn relative_path(
+    module: &parsed::TypePath,
+    path: &syn::TypePath,
+) -> Option<(parsed::TypePath, syn::TypePath, bool)> {
+    let mut start = module.clone();
+    let mut rest = path.clone();
+    let mut outwards = true;
+    loop {
+        let first = match rest.path.segments.first() {
+            Some(x) if rest.path.segments.len() > 1 => x.ident.to_string(),
+            _ => break,
+        };
+        match first.as_str() {
+            "crate" => start = parsed::TypePath::new(),
+            "self" => {}
+            "super" => start = start.parent()?,
+            _ => break,
+        }
+        outwards = false;
+        rest.path.segments = rest.path.segments.into_iter().skip(1).collect();
+    }
+    Some((start, rest, outwards))
+}
+
+fn internal_error(path: &parsed::TypePath, message: &str) -> Error {
+    Error::new(
+        proc_macro2::Span::call_site(),
+        format!(
+            "internal pahole error while laying out `{}`: {}",
+            path, message
+        ),
+    )
+}
+
+/// Like `parse_repr`, but for structs and unions, which can't have an integer `repr`.
+fn parse_struct_repr(attrs: &[syn::Attribute]) -> Result<parsed::Repr, Error> {
+    let repr = parse_repr(attrs)?;
+    match &repr.int {
+        Some(int) => Err(Error::new_spanned(
+            int,
+            "integer `repr`s can only be used on enums",
+        )),
+        None => Ok(repr),
+    }
+}
+
+/// `repr(simd)` vectors need all of their elements to have the same type, either as separate
+/// fields or as a single array field.
+fn check_simd_fields(fields: &[(String, Type)]) -> Result<(), Error> {
+    if let Some((_, first)) = fields.first() {
+        for (_, ty) in &fields[1..] {
+            if ty != first {
+                return Err(Error::new_spanned(
+                    ty,
+                    "`repr(simd)` requires all fields to have the same type",
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Rust and C structs never have padding before their first field.
+fn check_first_offset(
+    path: &parsed::TypePath,
+    fields: &[layout::FieldLayout],
+) -> Result<(), Error> {
+    match fields.first() {
+        Some(field) if field.offset != 0 => Err(internal_error(
+            path,
+            &format!("first field `{}` is at offset {}", field.name, field.offset),
+        )),
+        _ => Ok(()),
+    }
+}
+
+impl Data {
+    pub fn new() -> Self {
+        let mut self_ = Self::without_builtins();
+        self_.builtins = true;
+        self_.add_builtins();
+        self_
+    }
+
+    /// Creates a `Data` that knows no types, not even primitives, so that all of them have to be
+    /// registered with `add_layout`.
+    pub fn without_builtins() -> Self {
+        Self {
+            unprocessed_items: HashMap::new(),
+            processed_items: HashMap::new(),
+            niches: HashMap::new(),
+            ffi_unsafe: HashMap::new(),
+            zero_invalid: HashMap::new(),
+            drop_glue: HashMap::new(),
+            item_order: Vec::new(),
+            imports: HashMap::new(),
+            type_numbers: HashMap::new(),
+            custom_layouts: HashMap::new(),
+            custom_niches: HashMap::new(),
+            target: layout::Target::host(),
+            features: None,
+            struct_definitions: HashMap::new(),
+            in_progress: HashSet::new(),
+            std_layouts_used: HashSet::new(),
+            dependencies: HashMap::new(),
+            default_instantiations: HashMap::new(),
+            opaque_types: HashSet::new(),
+            generic_templates: HashMap::new(),
+            unknown_types: None,
+            assumed_types: Vec::new(),
+            builtins: false,
+        }
+    }
+
+    fn add_builtins(&mut self) {
+        use std::num::{
+            NonZeroI128, NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8, NonZeroIsize, NonZeroU128,
+            NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU8, NonZeroUsize,
+        };
+        impl_add_builtins! { self; u8 u16 u32 u64 u128 usize i8 i16 i32 i64 i128 isize f32 f64 bool char }
+        impl_add_builtins! { self;
+            NonZeroU8 NonZeroU16 NonZeroU32 NonZeroU64 NonZeroU128 NonZeroUsize
+            NonZeroI8 NonZeroI16 NonZeroI32 NonZeroI64 NonZeroI128 NonZeroIsize
+        }
+        impl_add_niches! { self;
+            bool => 254,
+            char => (1 << 32) - (char::MAX as u128 + 1),
+            NonZeroU8 => 1, NonZeroU16 => 1, NonZeroU32 => 1, NonZeroU64 => 1, NonZeroU128 => 1,
+            NonZeroUsize => 1, NonZeroI8 => 1, NonZeroI16 => 1, NonZeroI32 => 1, NonZeroI64 => 1,
+            NonZeroI128 => 1, NonZeroIsize => 1,
+        }
+        self.ffi_unsafe.insert(
+            parsed::TypePath(parse_quote!(char)),
+            "`char` has no C equivalent".to_string(),
+        );
+        for name in &[
+            "NonZeroU8",
+            "NonZeroU16",
+            "NonZeroU32",
+            "NonZeroU64",
+            "NonZeroU128",
+            "NonZeroUsize",
+            "NonZeroI8",
+            "NonZeroI16",
+            "NonZeroI32",
+            "NonZeroI64",
+            "NonZeroI128",
+            "NonZeroIsize",
+        ] {
+            let ident = syn::Ident::new(name, proc_macro2::Span::call_site());
+            self.zero_invalid.insert(
+                parsed::TypePath(parse_quote!(#ident)),
+                format!("`{}` can't be zero", name),
+            );
+        }
+        for x in std_layouts::STD_LAYOUTS {
+            let path = parsed::TypePath(syn::parse_str(x.path).unwrap());
+            let layout = Layout::from_size_align(x.size, x.align).unwrap();
+            self.processed_items.insert(path.clone(), layout);
+            self.niches.insert(path.clone(), x.niche);
+            self.ffi_unsafe
+                .insert(path, format!("`{}` has no guaranteed layout", x.path));
+        }
+        for name in &["usize", "isize", "NonZeroUsize", "NonZeroIsize"] {
+            let ident = syn::Ident::new(name, proc_macro2::Span::call_site());
+            self.processed_items.insert(
+                parsed::TypePath(parse_quote!(#ident)),
+                self.target.pointer(),
+            );
+        }
+    }
+
+    /// Sets the target whose layouts are computed. Call `clear_processed` afterwards if any
+    /// layouts have already been computed for the previous target.
+    pub fn set_target(&mut self, target: layout::Target) {
+        self.target = target;
+    }
+
+    /// Evaluates `#[cfg(feature = "...")]` with `features` enabled and all others disabled. Call
+    /// it before adding any items.
+    pub fn set_features(&mut self, features: HashSet<String>) {
+        self.features = Some(features);
+    }
+
+    /// Lays out types that can't be found according to `unknown_types` instead of failing.
+    pub fn set_unknown_types(&mut self, unknown_types: options::UnknownTypes) {
+        self.unknown_types = Some(unknown_types);
+    }
+
+    /// Forgets all computed layouts so they can be recomputed, e.g. for another target, keeping
+    /// the added items and registered layouts.
+    pub fn clear_processed(&mut self) {
+        self.processed_items.clear();
+        self.niches.clear();
+        self.ffi_unsafe.clear();
+        self.zero_invalid.clear();
+        self.drop_glue.clear();
+        if self.builtins {
+            self.add_builtins();
+        }
+        self.processed_items.extend(
+            self.custom_layouts
+                .iter()
+                .map(|(path, layout)| (path.clone(), *layout)),
+        );
+    }
+
+    /// Lays out the added items for each of `Target::POINTER_WIDTHS`, then again for the current
+    /// target.
+    fn resolve_for_each_pointer_width(&mut self) -> Result<Vec<Vec<layout::ItemLayout>>, Error> {
+        let target = self.target;
+        let mut layouts = Vec::new();
+        for &pointer_width in &layout::Target::POINTER_WIDTHS {
+            self.set_target(layout::Target { pointer_width });
+            self.clear_processed();
+            layouts.push(self.resolve()?);
+        }
+        self.set_target(target);
+        self.clear_processed();
+        self.resolve()?;
+        Ok(layouts)
+    }
+
+    /// Registers the layout of a type that can't be computed, e.g. one from another crate.
+    pub fn add_layout(&mut self, path: parsed::TypePath, layout: Layout) {
+        self.custom_layouts.insert(path.clone(), layout);
+        self.processed_items.insert(path, layout);
+    }
+
+    /// Registers that `path` has `niche` invalid values, e.g. 1 for a handle that's never null, so
+    /// that `Option` and other enums around it can store their tag in them.
+    pub fn register_niche(&mut self, path: parsed::TypePath, niche: u128) {
+        self.custom_niches.insert(path, niche);
+    }
+
+    /// Registers `path` as a type-level number, like `typenum::U16`, so that `GenericArray<T, N>`
+    /// can be laid out as `[T; N]`.
+    pub fn add_type_number(&mut self, path: parsed::TypePath, value: usize) {
+        self.type_numbers.insert(path, value);
+    }
+
+    /// The number `ty` stands for, if it's a registered type-level number.
+    fn type_number(&self, owner: &parsed::TypePath, ty: &Type) -> Option<usize> {
+        let path = match ty {
+            Type::Path(x) => x,
+            _ => return None,
+        };
+        let resolved = self.lookup(owner, path);
+        resolved
+            .and_then(|x| self.type_numbers.get(&x))
+            .or_else(|| self.type_numbers.get(&parsed::TypePath(path.clone())))
+            .copied()
+    }
+
+    pub fn add_item(&mut self, parent_path: parsed::TypePath, mut item: Item) -> Result<(), Error> {
+        if !cfg::strip(&mut item, &self.target, self.features.as_ref())? {
+            return Ok(());
+        }
+        let params = match &item {
+            Item::Struct(x) => generics::params(&x.generics),
+            Item::Enum(x) => generics::params(&x.generics),
+            Item::Union(x) => generics::params(&x.generics),
+            Item::Type(x) => generics::params(&x.generics),
+            _ => Vec::new(),
+        };
+        let mut definition = None;
+        let (ident, parsed_item) = match item {
+            Item::Mod(x) => {
+                if let Some((_, items)) = x.content {
+                    let ident = x.ident;
+                    let mut path: parsed::TypePath = parent_path;
+                    path.push(ident.into());
+                    for sub_item in items {
+                        self.add_item(path.clone(), sub_item)?;
+                    }
+                } else {
+                    return Err(Error::new_spanned(
+                        x,
+                        "pahole does not currently support `mod`s implemented in other files",
+                    ));
+                }
+                return Ok(());
+            }
+            Item::Enum(x) => (
+                x.ident,
+                parsed::Item::Enum(
+                    {
+                        let mut next = 0;
+                        x.variants
+                            .into_iter()
+                            .map(|y| {
+                                let discriminant = match &y.discriminant {
+                                    Some((_, expr)) => discriminant_value(expr)?,
+                                    None => next,
+                                };
+                                next = discriminant + 1;
+                                Ok((y.ident, parse_struct_fields(y.fields)?, discriminant))
+                            })
+                            .collect::<Result<_, Error>>()?
+                    },
+                    parse_repr(&x.attrs)?,
+                ),
+            ),
+            Item::Struct(x) => {
+                definition = Some(x.clone());
+                let repr = parse_struct_repr(&x.attrs)?;
+                (
+                    x.ident,
+                    parsed::Item::Struct(parse_struct_fields(x.fields)?, repr),
+                )
+            }
+            Item::Type(x) => (x.ident, parsed::Item::TypeAlias(*x.ty)),
+            Item::Const(x) => (x.ident, parsed::Item::Value(*x.ty)),
+            Item::Static(x) => (x.ident, parsed::Item::Value(*x.ty)),
+            Item::Use(x) => {
+                let prefix = syn::TypePath {
+                    qself: None,
+                    path: syn::Path {
+                        leading_colon: x.leading_colon,
+                        segments: syn::punctuated::Punctuated::new(),
+                    },
+                };
+                self.add_use(&parent_path, prefix, &x.tree);
+                return Ok(());
+            }
+            Item::ForeignMod(x) => {
+                for foreign_item in x.items {
+                    self.add_foreign_item(&parent_path, foreign_item)?;
+                }
+                return Ok(());
+            }
+            Item::Union(x) => (
+                x.ident,
+                parsed::Item::Union(
+                    x.fields
+                        .named
+                        .into_iter()
+                        .map(named_field)
+                        .collect::<Result<_, _>>()?,
+                    parse_struct_repr(&x.attrs)?,
+                ),
+            ),
+            _ => {
+                return Err(Error::new_spanned(item, "pahole can currently only process `mod`s, `use`s, `enum`s, `struct`s, `type`s, `union`s, `const`s, `static`s, and `extern` blocks."));
+            }
+        };
+        let mut parsed_item = parsed_item;
+        let label = format!("{}", ident);
+        let mut path = parent_path;
+        path.push(ident.into());
+        if !params.is_empty() {
+            self.generic_templates
+                .insert(path.clone(), (params.clone(), parsed_item.clone()));
+            let defaults = match generics::defaults(&params) {
+                Some(x) => x,
+                None => {
+                    eprintln!(
+                        "note: `{}` is generic, so pahole only lays out its instantiations",
+                        path
+                    );
+                    return Ok(());
+                }
+            };
+            let label = format!(
+                "{}<{}>",
+                label,
+                defaults
+                    .iter()
+                    .map(|(_, ty)| report::tokens_to_string(ty))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            generics::substitute_item(&mut parsed_item, &defaults.into_iter().collect());
+            self.default_instantiations.insert(path.clone(), label);
+        }
+        self.item_order.push(path.clone());
+        if let Some(definition) = definition {
+            self.struct_definitions.insert(path.clone(), definition);
+        }
+        self.unprocessed_items.insert(path, parsed_item);
+        Ok(())
+    }
+
+    /// Records a declaration from an `extern` block. Statics are reported like other statics, and
+    /// types are opaque, so only pointers to them can be laid out.
+    fn add_foreign_item(
+        &mut self,
+        parent_path: &parsed::TypePath,
+        item: syn::ForeignItem,
+    ) -> Result<(), Error> {
+        let mut path = parent_path.clone();
+        match item {
+            syn::ForeignItem::Static(x) => {
+                if cfg::enabled(&x.attrs, &self.target, self.features.as_ref())? {
+                    path.push(x.ident.into());
+                    self.item_order.push(path.clone());
+                    self.unprocessed_items
+                        .insert(path, parsed::Item::Value(*x.ty));
+                }
+            }
+            syn::ForeignItem::Type(x) => {
+                if cfg::enabled(&x.attrs, &self.target, self.features.as_ref())? {
+                    path.push(x.ident.into());
+                    self.opaque_types.insert(path);
+                }
+            }
+            // Functions have no layout.
+            syn::ForeignItem::Fn(_) => {}
+            item => eprintln!(
+                "warning: pahole skips `{}` in `extern` blocks",
+                report::tokens_to_string(&item)
+            ),
+        }
+        Ok(())
+    }
+
+    /// Finds the item a type path used inside the item `owner` refers to, searching outwards
+    /// from the module containing `owner`.
+    fn lookup(&self, owner: &parsed::TypePath, path: &syn::TypePath) -> Option<parsed::TypePath> {
+        if path.path.is_ident("Self") {
+            return Some(owner.clone());
+        }
+        let module = owner.parent().unwrap_or_default();
+        self.lookup_in(&module, path, 0)
+            .or_else(|| self.lookup_primitive(path))
+            .or_else(|| self.lookup_instance(owner, path))
+    }
+
+    /// Finds the instantiation of a generic item that `path` names, like `Wrapper<u32>`. It's
+    /// keyed by the item's path with the arguments rewritten to start at the crate root, and
+    /// added by `instantiate` once it's laid out.
+    fn lookup_instance(
+        &self,
+        owner: &parsed::TypePath,
+        path: &syn::TypePath,
+    ) -> Option<parsed::TypePath> {
+        let mut base = path.clone();
+        let arguments = match &mut base.path.segments.last_mut()?.arguments {
+            x @ syn::PathArguments::AngleBracketed(_) => {
+                std::mem::replace(x, syn::PathArguments::None)
+            }
+            _ => return None,
+        };
+        let base = self.lookup(owner, &base)?;
+        if !self.generic_templates.contains_key(&base) {
+            return None;
+        }
+        let mut arguments = match arguments {
+            syn::PathArguments::AngleBracketed(x) => x,
+            _ => unreachable!(),
+        };
+        // Lifetimes don't affect layouts, so leaving them out lets `S<'a, T>` and `S<'b, T>` share
+        // an instantiation.
+        arguments.args = std::mem::take(&mut arguments.args)
+            .into_iter()
+            .filter(|x| !matches!(x, syn::GenericArgument::Lifetime(_)))
+            .collect();
+        let resolve = |x: &syn::TypePath| {
+            let found = self.lookup(owner, x)?;
+            let builtin = found.0 == *x && !self.unprocessed_items.contains_key(&found);
+            if builtin {
+                None
+            } else {
+                Some(found)
+            }
+        };
+        for argument in &mut arguments.args {
+            if let syn::GenericArgument::Type(ty) = argument {
+                generics::canonicalize(ty, &resolve);
+            }
+        }
+        let mut instance = base;
+        instance.0.path.segments.last_mut()?.arguments =
+            syn::PathArguments::AngleBracketed(arguments);
+        Some(instance)
+    }
+
+    /// Lays out the instantiation of a generic item that `ty` names, e.g. `Buf<16>`, resolving
+    /// paths from the root of the added items.
+    pub fn monomorphize(&mut self, ty: &Type) -> Result<layout::ItemLayout, Error> {
+        let path = match ty {
+            Type::Path(x) => self.lookup_instance(&parsed::TypePath::new(), x),
+            _ => None,
+        }
+        .ok_or_else(|| Error::new_spanned(ty, "expected an instantiation of a generic item"))?;
+        self.instantiate(&path, ty)?;
+        self.item_layout(&path)
+    }
+
+    /// The layout of the generic instantiation an alias like `type Handle = Wrapper<u32>` names.
+    fn alias_instance(
+        &mut self,
+        alias: &layout::ItemLayout,
+    ) -> Result<Option<layout::ItemLayout>, Error> {
+        let instance = match &alias.shape {
+            layout::Shape::TypeAlias(Type::Path(x)) => self.lookup_instance(&alias.path, x),
+            _ => None,
+        };
+        match instance {
+            Some(path) => self.item_layout(&path).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Replaces the aliases in the field types of `item` with the types they alias, for reports
+    /// that name the concrete types.
+    fn expand_aliases(&self, item: &mut layout::ItemLayout) {
+        struct Expand<'a> {
+            data: &'a Data,
+            owner: parsed::TypePath,
+        }
+        impl syn::visit_mut::VisitMut for Expand<'_> {
+            fn visit_type_mut(&mut self, ty: &mut Type) {
+                if let Type::Path(x) = ty {
+                    let alias = self.data.lookup(&self.owner, x);
+                    if let Some(path) = alias {
+                        if let Some(parsed::Item::TypeAlias(target)) =
+                            self.data.unprocessed_items.get(&path)
+                        {
+                            *ty = target.clone();
+                            // The aliased type is written relative to the alias.
+                            let owner = std::mem::replace(&mut self.owner, path);
+                            self.visit_type_mut(ty);
+                            self.owner = owner;
+                            return;
+                        }
+                    }
+                }
+                syn::visit_mut::visit_type_mut(self, ty);
+            }
+        }
+        let mut expand = Expand {
+            data: self,
+            owner: item.path.clone(),
+        };
+        let fields: Vec<&mut layout::FieldLayout> = match &mut item.shape {
+            layout::Shape::Struct(x) => x.fields.iter_mut().collect(),
+            layout::Shape::Enum { variants, .. } => variants
+                .iter_mut()
+                .flat_map(|x| x.payload.fields.iter_mut())
+                .collect(),
+            layout::Shape::Union(x) => x.iter_mut().collect(),
+            layout::Shape::TypeAlias(_) | layout::Shape::Value(_) => Vec::new(),
+        };
+        for field in fields {
+            syn::visit_mut::VisitMut::visit_type_mut(&mut expand, &mut field.ty);
+        }
+    }
+
+    /// The fields of struct `item` that box another analyzed item, each with that item's layout
+    /// and the layout `item` would have with the field inline instead.
+    fn boxed_fields(
+        &mut self,
+        item: &layout::ItemLayout,
+    ) -> Result<Vec<(usize, Layout, layout::StructLayout)>, Error> {
+        let (layout, repr) = match (&item.shape, self.unprocessed_items.get(&item.path)) {
+            (layout::Shape::Struct(x), Some(parsed::Item::Struct(_, repr))) => (x, repr.clone()),
+            _ => return Ok(Vec::new()),
+        };
+        let mut boxed = Vec::new();
+        for (i, field) in layout.fields.iter().enumerate() {
+            let inner = match &field.ty {
+                Type::Path(x) => match generic_argument(x, "Box") {
+                    Some(Type::Path(y)) => self.lookup(&item.path, y),
+                    _ => None,
+                },
+                _ => None,
+            };
+            let inner = match inner {
+                // A recursive type can only be boxed.
+                Some(x) if x != item.path && self.unprocessed_items.contains_key(&x) => x,
+                _ => continue,
+            };
+            let inline = self.item_layout(&inner)?.total;
+            let fields = layout
+                .fields
+                .iter()
+                .enumerate()
+                .map(|(j, x)| {
+                    let layout = if i == j { inline } else { x.layout };
+                    (x.name.clone(), x.ty.clone(), layout)
+                })
+                .collect();
+            let inlined = self.place_fields(&item.path, fields, &repr, layout.unsized_tail)?;
+            boxed.push((i, inline, inlined));
+        }
+        Ok(boxed)
+    }
+
+    /// The fields of `item`, if it's a struct, whose types are added unions, with the unions'
+    /// fields.
+    fn union_fields(
+        &mut self,
+        item: &layout::ItemLayout,
+    ) -> Result<Vec<(usize, Vec<layout::FieldLayout>)>, Error> {
+        let fields = match &item.shape {
+            layout::Shape::Struct(x) => &x.fields,
+            _ => return Ok(Vec::new()),
+        };
+        let mut unions = Vec::new();
+        for (i, field) in fields.iter().enumerate() {
+            let path = match &field.ty {
+                Type::Path(x) => self.lookup(&item.path, x),
+                _ => None,
+            };
+            let path = match path {
+                Some(x)
+                    if matches!(
+                        self.unprocessed_items.get(&x),
+                        Some(parsed::Item::Union(..))
+                    ) =>
+                {
+                    x
+                }
+                _ => continue,
+            };
+            if let layout::Shape::Union(x) = self.item_layout(&path)?.shape {
+                unions.push((i, x));
+            }
+        }
+        Ok(unions)
+    }
+
+    /// Adds the item `path` names if it's an instantiation of a generic item that hasn't been
+    /// used before.
+    fn instantiate(&mut self, path: &parsed::TypePath, ty: &Type) -> Result<(), Error> {
+        if self.unprocessed_items.contains_key(path) || self.processed_items.contains_key(path) {
+            return Ok(());
+        }
+        let mut base = path.clone();
+        let arguments = match base.0.path.segments.last_mut() {
+            Some(last) => std::mem::replace(&mut last.arguments, syn::PathArguments::None),
+            None => return Ok(()),
+        };
+        let (params, template) = match self.generic_templates.get(&base) {
+            Some(x) => x.clone(),
+            None => return Ok(()),
+        };
+        let arguments: Vec<syn::GenericArgument> = match arguments {
+            syn::PathArguments::AngleBracketed(x) => x
+                .args
+                .into_iter()
+                .filter(|y| !matches!(y, syn::GenericArgument::Lifetime(_)))
+                .collect(),
+            _ => Vec::new(),
+        };
+        if arguments.len() > params.len() {
+            return Err(Error::new_spanned(ty, "too many generic arguments"));
+        }
+        // Type and const arguments are told apart by the parameters they're in the place of.
+        let arguments = arguments
+            .into_iter()
+            .zip(&params)
+            .map(|(argument, (ident, kind, _))| {
+                let kind_name = match kind {
+                    generics::Kind::Type => "type",
+                    generics::Kind::Const => "const",
+                };
+                generics::argument(argument, *kind).ok_or_else(|| {
+                    Error::new_spanned(
+                        ty,
+                        format!("expected a {} argument for `{}`", kind_name, ident),
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let bound = generics::bind(&params, arguments).map_err(|x| {
+            Error::new_spanned(ty, format!("missing a generic argument for `{}`", x))
+        })?;
+        let mut item = template;
+        generics::substitute_item(&mut item, &bound.into_iter().collect());
+        self.unprocessed_items.insert(path.clone(), item);
+        Ok(())
+    }
+
+    /// Resolves paths into the `std::primitive` and `core::primitive` modules to the builtins.
+    fn lookup_primitive(&self, path: &syn::TypePath) -> Option<parsed::TypePath> {
+        let segments: Vec<&syn::PathSegment> = path.path.segments.iter().collect();
+        match segments.as_slice() {
+            [krate, module, name]
+                if (krate.ident == "std" || krate.ident == "core")
+                    && module.ident == "primitive"
+                    && path.qself.is_none() =>
+            {
+                let ident = &name.ident;
+                let builtin = parsed::TypePath(parse_quote!(#ident));
+                if self.processed_items.contains_key(&builtin) {
+                    Some(builtin)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn is_type(&self, path: &parsed::TypePath) -> bool {
+        self.processed_items.contains_key(path)
+            || self.opaque_types.contains(path)
+            || self.generic_templates.contains_key(path)
+            || matches!(
+                self.unprocessed_items.get(path),
+                Some(x) if !matches!(x, parsed::Item::Value(_))
+            )
+    }
+
+    /// Finds the item `path` refers to from within `module`, following `use` imports.
+    fn lookup_in(
+        &self,
+        module: &parsed::TypePath,
+        path: &syn::TypePath,
+        depth: usize,
+    ) -> Option<parsed::TypePath> {
+        let (start, path, outwards) = relative_path(module, path)?;
+        let path = parsed::TypePath(path);
+        let mut scope = Some(start);
+        while let Some(current) = scope {
+            let candidate = current.concat(path.clone());
+            if self.is_type(&candidate) {
+                return Some(candidate);
+            }
+            if let Some((alias_module, target)) = self.imports.get(&candidate) {
+                if depth < MAX_IMPORT_DEPTH {
+                    if let Some(found) = self.lookup_in(alias_module, target, depth + 1) {
+                        return Some(found);
+                    }
+                }
+            }
+            if !outwards {
+                break;
+            }
+            scope = current.parent();
+        }
+        None
+    }
+
+    /// Records the names brought into `module` by a `use` item.
+    fn add_use(&mut self, module: &parsed::TypePath, prefix: syn::TypePath, tree: &syn::UseTree) {
+        let extended = |ident: &syn::Ident| {
+            let mut path = prefix.clone();
+            path.path.segments.push(ident.clone().into());
+            path
+        };
+        match tree {
+            syn::UseTree::Path(x) => {
+                let prefix = extended(&x.ident);
+                self.add_use(module, prefix, &x.tree);
+            }
+            syn::UseTree::Name(x) => {
+                // `use a::b::{self}` imports `b` itself.
+                let (name, target) = if x.ident == "self" {
+                    match prefix.path.segments.last() {
+                        Some(last) => (last.ident.clone(), prefix.clone()),
+                        None => return,
+                    }
+                } else {
+                    (x.ident.clone(), extended(&x.ident))
+                };
+                let mut alias = module.clone();
+                alias.push(name.into());
+                self.imports.insert(alias, (module.clone(), target));
+            }
+            syn::UseTree::Rename(x) => {
+                let mut alias = module.clone();
+                alias.push(x.rename.clone().into());
+                let target = extended(&x.ident);
+                self.imports.insert(alias, (module.clone(), target));
+            }
+            syn::UseTree::Group(x) => {
+                for tree in &x.items {
+                    self.add_use(module, prefix.clone(), tree);
+                }
+            }
+            // Glob imports would need the full list of names in the imported module.
+            syn::UseTree::Glob(_) => {}
+        }
+    }
+
+    fn type_layout(&mut self, owner: &parsed::TypePath, ty: &Type) -> Result<Layout, Error> {
+        match ty {
+            Type::Path(x) => {
+                if let Some(layout) = self.std_type_layout(owner, x)? {
+                    return Ok(layout);
+                }
+                let path = match self.lookup(owner, x) {
+                    Some(path) => path,
+                    None => return self.unknown_type_layout(ty),
+                };
+                if std_layouts::is_std_layout(&path.to_string()) {
+                    self.std_layouts_used.insert(path.clone());
+                }
+                self.instantiate(&path, ty)?;
+                if self.unprocessed_items.contains_key(&path) {
+                    let dependencies = self.dependencies.entry(owner.clone()).or_default();
+                    if !dependencies.contains(&path) {
+                        dependencies.push(path.clone());
+                    }
+                }
+                self.path_layout(&path, ty)
+            }
+            Type::Paren(x) => self.type_layout(owner, &x.elem),
+            Type::Group(x) => self.type_layout(owner, &x.elem),
+            Type::Tuple(x) => {
+                let fields = self.field_layouts(
+                    owner,
+                    x.elems
+                        .iter()
+                        .enumerate()
+                        .map(|(i, y)| (i.to_string(), y.clone())),
+                )?;
+                Ok(self
+                    .place_fields(owner, fields, &parsed::Repr::default(), false)?
+                    .total)
+            }
+            Type::Array(x) => {
+                let elem = self.type_layout(owner, &x.elem)?;
+                let len = array_len(&x.len)?;
+                let size = elem
+                    .size()
+                    .checked_mul(len)
+                    .ok_or_else(|| Error::new_spanned(ty, "array is too large"))?;
+                Ok(Layout::from_size_align(size, elem.align()).unwrap())
+            }
+            Type::Reference(syn::TypeReference { elem, .. })
+            | Type::Ptr(syn::TypePtr { elem, .. }) => Ok(self.pointer_layout(owner, elem)),
+            Type::BareFn(_) => Ok(self.target.pointer()),
+            Type::Slice(_) | Type::TraitObject(_) => Err(unsized_error(ty)),
+            _ => Err(Error::new_spanned(
+                ty,
+                "pahole does not currently support this kind of type",
+            )),
+        }
+    }
+
+    /// The layout assumed for a type that can't be found, if `unknown_types` allows one.
+    fn unknown_type_layout(&mut self, ty: &Type) -> Result<Layout, Error> {
+        let name = report::tokens_to_string(ty);
+        let pointer = self.target.pointer();
+        let layout = match self.unknown_types {
+            None => {
+                return Err(Error::new_spanned(
+                    ty,
+                    format!("pahole could not find the type `{}`", name),
+                ))
+            }
+            Some(options::UnknownTypes::Pointer) => pointer,
+            Some(options::UnknownTypes::Skip) => Layout::new::<()>(),
+            Some(options::UnknownTypes::Size(size)) => {
+                let align = (1 << size.trailing_zeros().min(usize::BITS - 1)).min(pointer.align());
+                Layout::from_size_align(size, align).unwrap()
+            }
+        };
+        if !self.assumed_types.contains(&name) {
+            self.assumed_types.push(name);
+        }
+        Ok(layout)
+    }
+
+    /// The layout of a pointer to `pointee`, which is twice as large when `pointee` is unsized.
+    fn pointer_layout(&self, owner: &parsed::TypePath, pointee: &Type) -> Layout {
+        if self.is_unsized(owner, pointee) {
+            self.target.fat_pointer()
+        } else {
+            self.target.pointer()
+        }
+    }
+
+    /// Whether `ty` is a slice, `str`, trait object, or a struct ending in one of those.
+    fn is_unsized(&self, owner: &parsed::TypePath, ty: &Type) -> bool {
+        let mut seen = HashSet::new();
+        let mut owner = owner.clone();
+        let mut ty = ty;
+        loop {
+            match ty {
+                Type::Slice(_) | Type::TraitObject(_) => return true,
+                Type::Paren(x) => ty = &x.elem,
+                Type::Group(x) => ty = &x.elem,
+                Type::Path(x) => {
+                    let path = match self.lookup(&owner, x) {
+                        Some(path) => path,
+                        None => return x.path.is_ident("str"),
+                    };
+                    let last = match self.unprocessed_items.get(&path) {
+                        Some(parsed::Item::Struct(parsed::Struct::Tuple(fields), _)) => {
+                            fields.last()
+                        }
+                        Some(parsed::Item::Struct(parsed::Struct::Struct(fields), _)) => {
+                            fields.last().map(|(_, y)| y)
+                        }
+                        _ => None,
+                    };
+                    match last {
+                        // Guards against structs that contain themselves, which are errors anyway.
+                        Some(last) if seen.insert(path.clone()) => {
+                            ty = last;
+                            owner = path;
+                        }
+                        _ => return false,
+                    }
+                }
+                _ => return false,
+            }
+        }
+    }
+
+    /// Like `field_layouts`, but allowing an unsized last field, which only contributes its
+    /// alignment where that's known.
+    fn struct_field_layouts(
+        &mut self,
+        owner: &parsed::TypePath,
+        mut fields: Vec<(String, Type)>,
+    ) -> Result<Vec<(String, Type, Layout)>, Error> {
+        let tail = match fields.last() {
+            Some((_, ty)) if self.is_unsized(owner, ty) => fields.pop(),
+            _ => None,
+        };
+        let mut layouts = self.field_layouts(owner, fields)?;
+        if let Some((name, ty)) = tail {
+            let layout = match &ty {
+                Type::Slice(x) => {
+                    let elem = self.type_layout(owner, &x.elem)?;
+                    Layout::from_size_align(0, elem.align()).unwrap()
+                }
+                Type::Path(x) if self.lookup(owner, x).is_some() => self.type_layout(owner, &ty)?,
+                // `str` and trait objects, whose alignment is unknown.
+                _ => Layout::new::<()>(),
+            };
+            layouts.push((name, ty, layout));
+        }
+        Ok(layouts)
+    }
+
+    /// Handles the standard library types with a known layout, unless shadowed by a user item.
+    fn std_type_layout(
+        &mut self,
+        owner: &parsed::TypePath,
+        path: &syn::TypePath,
+    ) -> Result<Option<Layout>, Error> {
+        if self.lookup(owner, path).is_some() {
+            return Ok(None);
+        }
+        if path.path.is_ident("str") {
+            return Err(unsized_error(path));
+        }
+        if let Some(pointee) = non_null_pointee(path) {
+            return Ok(Some(self.pointer_layout(owner, pointee)));
+        }
+        // Zero-sized with alignment 1 whatever the argument is, unlike `[T; 0]`.
+        if generic_argument(path, "PhantomData").is_some() {
+            return Ok(Some(Layout::new::<()>()));
+        }
+        if let Some(inner) =
+            generic_argument(path, "ManuallyDrop").or_else(|| generic_argument(path, "MaybeUninit"))
+        {
+            return Ok(Some(self.type_layout(owner, inner)?));
+        }
+        if let Some([elem, len]) = generic_arguments(path, "GenericArray").as_deref() {
+            if let Some(len) = self.type_number(owner, len) {
+                let array: Type = parse_quote!([#elem; #len]);
+                return Ok(Some(self.type_layout(owner, &array)?));
+            }
+        }
+        if is_heap_buffer(path) {
+            let pointer = self.target.pointer();
+            let size = 3 * pointer.size();
+            return Ok(Some(
+                Layout::from_size_align(size, pointer.align()).unwrap(),
+            ));
+        }
+        if let Some((ok, err)) = result_arguments(path) {
+            return Ok(Some(self.result_layout(owner, ok, err)?.0));
+        }
+        if let Some(borrowed) = cow_argument(path) {
+            return Ok(Some(self.cow_layout(owner, path, borrowed)?.0));
+        }
+        if let Some(inner) = generic_argument(path, "Option") {
+            let layout = self.type_layout(owner, inner)?;
+            if self.type_niche(owner, inner)? != 0 {
+                return Ok(Some(layout));
+            }
+            let fields = vec![(String::new(), inner.clone(), layout)];
+            return Ok(Some(
+                layout::StructLayout::sequential(Layout::new::<u8>(), fields).total,
+            ));
+        }
+        Ok(None)
+    }
+
+    /// The layout and niche of `Result<ok, err>`, which is laid out like a user enum with
+    /// variants `Ok(ok)` and `Err(err)`.
+    fn result_layout(
+        &mut self,
+        owner: &parsed::TypePath,
+        ok: &Type,
+        err: &Type,
+    ) -> Result<(Layout, u128), Error> {
+        let names = vec![parse_quote!(Ok), parse_quote!(Err)];
+        let fields = vec![
+            vec![("0".to_string(), ok.clone())],
+            vec![("0".to_string(), err.clone())],
+        ];
+        let (_, total, niche) = self.enum_layout(owner, names, vec![0, 1], fields)?;
+        Ok((total, niche))
+    }
+
+    /// The layout and niche of `path`, a `Cow<B>` borrowing `borrowed`. Like `Option<String>`,
+    /// it's the size of the owned form, which stores the discriminant in its capacity and leaves
+    /// the rest of its bytes to the borrowed pointer.
+    fn cow_layout(
+        &mut self,
+        owner: &parsed::TypePath,
+        path: &syn::TypePath,
+        borrowed: &Type,
+    ) -> Result<(Layout, u128), Error> {
+        let owned = cow_owned(path, borrowed)?;
+        // `Borrowed` takes one of the owned form's invalid capacities.
+        Ok((
+            self.type_layout(owner, &owned)?,
+            self.target.capacity_niche() - 1,
+        ))
+    }
+
+    /// The padding inside values of `ty` when it's a tuple or array, including nested ones and
+    /// the padding of each item in an array of items.
+    fn internal_padding(&mut self, owner: &parsed::TypePath, ty: &Type) -> Result<usize, Error> {
+        match ty {
+            Type::Paren(x) => self.internal_padding(owner, &x.elem),
+            Type::Group(x) => self.internal_padding(owner, &x.elem),
+            Type::Tuple(x) => {
+                let fields = self.field_layouts(
+                    owner,
+                    x.elems
+                        .iter()
+                        .enumerate()
+                        .map(|(i, y)| (i.to_string(), y.clone())),
+                )?;
+                let layout = self.place_fields(owner, fields, &parsed::Repr::default(), false)?;
+                let mut padding = layout.hole_bytes() + layout.padding;
+                for elem in &x.elems {
+                    padding += self.internal_padding(owner, elem)?;
+                }
+                Ok(padding)
+            }
+            Type::Array(x) => {
+                // Unlike a single value, an array of items repeats their padding in every element.
+                let elem = match &*x.elem {
+                    Type::Path(y) => match self.lookup(owner, y) {
+                        Some(path) if self.unprocessed_items.contains_key(&path) => {
+                            self.item_layout(&path)?.padding()
+                        }
+                        _ => 0,
+                    },
+                    elem => self.internal_padding(owner, elem)?,
+                };
+                Ok(elem * array_len(&x.len)?)
+            }
+            _ => Ok(0),
+        }
+    }
+
+    fn add_internal_padding(
+        &mut self,
+        owner: &parsed::TypePath,
+        layout: &mut layout::StructLayout,
+    ) -> Result<(), Error> {
+        for field in &mut layout.fields {
+            field.internal_padding = self.internal_padding(owner, &field.ty)?;
+        }
+        Ok(())
+    }
+
+    /// The number of invalid values of `ty` (see `Data::niches`).
+    ///
+    /// `Option<T>` uses one of `T`'s invalid values for `None`, leaving one fewer for enclosing
+    /// types, so nested `Option`s stay the size of `T` until the niche runs out. After that, it
+    /// adds a `u8` tag, whose 254 unused values are a new niche.
+    fn type_niche(&mut self, owner: &parsed::TypePath, ty: &Type) -> Result<u128, Error> {
+        match ty {
+            Type::Path(x) => {
+                if self.lookup(owner, x).is_none() {
+                    if x.path.is_ident("str") {
+                        return Ok(0);
+                    }
+                    if non_null_pointee(x).is_some() {
+                        return Ok(1);
+                    }
+                    if is_heap_buffer(x) {
+                        return Ok(self.target.capacity_niche());
+                    }
+                    if let Some(inner) = generic_argument(x, "ManuallyDrop") {
+                        return self.type_niche(owner, inner);
+                    }
+                    if let Some((ok, err)) = result_arguments(x) {
+                        return Ok(self.result_layout(owner, ok, err)?.1);
+                    }
+                    if let Some(borrowed) = cow_argument(x) {
+                        return Ok(self.cow_layout(owner, x, borrowed)?.1);
+                    }
+                    if let Some(inner) = generic_argument(x, "Option") {
+                        return Ok(match self.type_niche(owner, inner)? {
+                            // The tag of `Option<T>` is a `u8` with two valid values.
+                            0 => 254,
+                            n => n - 1,
+                        });
+                    }
+                }
+                // Ensures the item has been processed.
+                self.type_layout(owner, ty)?;
+                Ok(match self.lookup(owner, x) {
+                    Some(path) => self
+                        .custom_niches
+                        .get(&path)
+                        .or_else(|| self.niches.get(&path))
+                        .copied()
+                        .unwrap_or(0),
+                    None => 0,
+                })
+            }
+            Type::Paren(x) => self.type_niche(owner, &x.elem),
+            Type::Group(x) => self.type_niche(owner, &x.elem),
+            Type::Tuple(x) => x
+                .elems
+                .iter()
+                .try_fold(0, |acc, elem| Ok(acc.max(self.type_niche(owner, elem)?))),
+            Type::Array(x) => Ok(if array_len(&x.len)? == 0 {
+                0
+            } else {
+                self.type_niche(owner, &x.elem)?
+            }),
+            Type::Reference(_) | Type::BareFn(_) => Ok(1),
+            _ => Ok(0),
+        }
+    }
+
+    /// Why `ty` can't be used in FFI, or `None` if it's FFI-safe.
+    fn type_ffi_unsafe(
+        &mut self,
+        owner: &parsed::TypePath,
+        ty: &Type,
+    ) -> Result<Option<String>, Error> {
+        match ty {
+            Type::Path(x) => {
+                if self.lookup(owner, x).is_none() {
+                    if x.path.is_ident("str") {
+                        return Ok(Some("`str` has no C equivalent".to_string()));
+                    }
+                    if let Some(pointee) = non_null_pointee(x) {
+                        return Ok(self.pointee_ffi_unsafe(owner, pointee));
+                    }
+                    if let Some(inner) = generic_argument(x, "ManuallyDrop")
+                        .or_else(|| generic_argument(x, "MaybeUninit"))
+                    {
+                        return self.type_ffi_unsafe(owner, inner);
+                    }
+                    if is_heap_buffer(x) {
+                        return Ok(Some(format!(
+                            "`{}` has no C equivalent",
+                            report::tokens_to_string(x)
+                        )));
+                    }
+                    if result_arguments(x).is_some() {
+                        return Ok(Some("`Result<T, E>` has no C equivalent".to_string()));
+                    }
+                    if cow_argument(x).is_some() {
+                        return Ok(Some("`Cow<B>` has no C equivalent".to_string()));
+                    }
+                    if let Some(inner) = generic_argument(x, "Option") {
+                        let nullable = is_non_null_pointer(inner) || is_non_zero(inner);
+                        return Ok(if nullable {
+                            self.type_ffi_unsafe(owner, inner)?
+                        } else {
+                            Some(
+                                "`Option<T>` is only FFI-safe when `T` is a non-null pointer or \
+                                 `NonZero` integer"
+                                    .to_string(),
+                            )
+                        });
+                    }
+                }
+                // Ensures the item has been processed.
+                self.type_layout(owner, ty)?;
+                Ok(match self.lookup(owner, x) {
+                    Some(path) => self.ffi_unsafe.get(&path).cloned(),
+                    None => None,
+                })
+            }
+            Type::Paren(x) => self.type_ffi_unsafe(owner, &x.elem),
+            Type::Group(x) => self.type_ffi_unsafe(owner, &x.elem),
+            Type::Array(x) => self.type_ffi_unsafe(owner, &x.elem),
+            Type::Tuple(x) if x.elems.is_empty() => Ok(None),
+            Type::Tuple(_) => Ok(Some("tuples have no C equivalent".to_string())),
+            Type::Reference(syn::TypeReference { elem, .. })
+            | Type::Ptr(syn::TypePtr { elem, .. }) => Ok(self.pointee_ffi_unsafe(owner, elem)),
+            Type::BareFn(x) => Ok(match &x.abi {
+                Some(abi) if abi.name.as_ref().is_none_or(|y| y.value() != "Rust") => None,
+                _ => Some(
+                    "`fn` pointers use the Rust calling convention; use `extern \"C\" fn`"
+                        .to_string(),
+                ),
+            }),
+            _ => Ok(Some(format!(
+                "`{}` has no C equivalent",
+                report::tokens_to_string(ty)
+            ))),
+        }
+    }
+
+    fn pointee_ffi_unsafe(&self, owner: &parsed::TypePath, pointee: &Type) -> Option<String> {
+        if !self.is_unsized(owner, pointee) {
+            None
+        } else {
+            Some("pointers to unsized types are twice as large as C pointers".to_string())
+        }
+    }
+
+    /// Why a struct or union with `fields` can't be used in FFI.
+    fn fields_ffi_unsafe(
+        &mut self,
+        path: &parsed::TypePath,
+        fields: &[(String, Type)],
+        repr: &parsed::Repr,
+    ) -> Result<Option<String>, Error> {
+        if !repr.c && !repr.transparent {
+            return Ok(Some(format!(
+                "`{}` has the default representation; add `#[repr(C)]`",
+                path
+            )));
+        }
+        for (name, ty) in fields {
+            if let Some(reason) = self.type_ffi_unsafe(path, ty)? {
+                return Ok(Some(format!(
+                    "field `{}` is not FFI-safe: {}",
+                    name, reason
+                )));
+            }
+        }
+        Ok(None)
+    }
+
+    fn path_layout(&mut self, path: &parsed::TypePath, ty: &Type) -> Result<Layout, Error> {
+        if let Some(layout) = self.processed_items.get(path) {
+            return Ok(*layout);
+        }
+        if self.opaque_types.contains(path) {
+            return Err(Error::new_spanned(
+                ty,
+                format!(
+                    "`{}` is an `extern` type with an unknown layout, so it can only be used \
+                     behind a pointer",
+                    path
+                ),
+            ));
+        }
+        if self.in_progress.contains(path) {
+            return Err(Error::new_spanned(
+                ty,
+                format!("`{}` contains itself and would have an infinite size", path),
+            ));
+        }
+        let layout = self.item_layout(path)?;
+        self.record(&layout);
+        Ok(layout.total)
+    }
+
+    /// Caches the properties of a computed item so that items using it don't recompute them.
+    fn record(&mut self, layout: &layout::ItemLayout) {
+        let path = layout.path.clone();
+        self.processed_items.insert(path.clone(), layout.total);
+        self.niches.insert(path.clone(), layout.niche);
+        match &layout.ffi_unsafe {
+            Some(reason) => self.ffi_unsafe.insert(path.clone(), reason.clone()),
+            None => self.ffi_unsafe.remove(&path),
+        };
+        match &layout.zero_invalid {
+            Some(reason) => self.zero_invalid.insert(path.clone(), reason.clone()),
+            None => self.zero_invalid.remove(&path),
+        };
+        match &layout.drop_glue {
+            Some(reason) => self.drop_glue.insert(path, reason.clone()),
+            None => self.drop_glue.remove(&path),
+        };
+    }
+
+    /// Why all-zero bytes aren't a valid value of `ty`, or `None` if they are.
+    fn type_zero_invalid(
+        &mut self,
+        owner: &parsed::TypePath,
+        ty: &Type,
+    ) -> Result<Option<String>, Error> {
+        match ty {
+            Type::Path(x) => {
+                if self.lookup(owner, x).is_none() {
+                    if non_null_pointee(x).is_some() || is_heap_buffer(x) {
+                        return Ok(Some(format!(
+                            "`{}` can't be null",
+                            report::tokens_to_string(x)
+                        )));
+                    }
+                    if let Some(inner) = generic_argument(x, "ManuallyDrop") {
+                        return self.type_zero_invalid(owner, inner);
+                    }
+                    // Any bytes are a valid `MaybeUninit`, including uninitialized ones.
+                    if generic_argument(x, "MaybeUninit").is_some() {
+                        return Ok(None);
+                    }
+                    if let Some(inner) = generic_argument(x, "Option") {
+                        // Zero is `None` when it's stored as a null pointer, a zero `NonZero`
+                        // integer, or a tag, and `Some` of a zero value otherwise.
+                        let reason = self.type_zero_invalid(owner, inner)?;
+                        if reason.is_none()
+                            || is_non_null_pointer(inner)
+                            || is_non_zero(inner)
+                            || self.type_niche(owner, inner)? == 0
+                        {
+                            return Ok(None);
+                        }
+                        return Ok(Some(format!(
+                            "`None` may not be stored as zero, and {}",
+                            reason.unwrap()
+                        )));
+                    }
+                    if let Some((ok, _)) = result_arguments(x) {
+                        return Ok(self
+                            .type_zero_invalid(owner, ok)?
+                            .map(|reason| format!("zero may not be `Ok`, and {}", reason)));
+                    }
+                    if let Some(borrowed) = cow_argument(x) {
+                        let owned = cow_owned(x, borrowed)?;
+                        return Ok(self
+                            .type_zero_invalid(owner, &owned)?
+                            .map(|reason| format!("zero may not be `Owned`, and {}", reason)));
+                    }
+                }
+                // Ensures the item has been processed.
+                self.type_layout(owner, ty)?;
+                Ok(match self.lookup(owner, x) {
+                    Some(path) if self.custom_layouts.contains_key(&path) => {
+                        Some(format!("the valid values of `{}` are unknown", path))
+                    }
+                    Some(path) => self.zero_invalid.get(&path).cloned(),
+                    None if self.assumed_types.contains(&report::tokens_to_string(ty)) => {
+                        Some(format!(
+                            "the valid values of `{}` are unknown",
+                            report::tokens_to_string(ty)
+                        ))
+                    }
+                    None => None,
+                })
+            }
+            Type::Paren(x) => self.type_zero_invalid(owner, &x.elem),
+            Type::Group(x) => self.type_zero_invalid(owner, &x.elem),
+            Type::Tuple(x) => {
+                for elem in &x.elems {
+                    if let Some(reason) = self.type_zero_invalid(owner, elem)? {
+                        return Ok(Some(reason));
+                    }
+                }
+                Ok(None)
+            }
+            Type::Array(x) if array_len(&x.len)? == 0 => Ok(None),
+            Type::Array(x) => self.type_zero_invalid(owner, &x.elem),
+            Type::Reference(_) => Ok(Some("references can't be null".to_string())),
+            Type::BareFn(_) => Ok(Some("`fn` pointers can't be null".to_string())),
+            _ => Ok(None),
+        }
+    }
+
+    /// Why dropping a `ty` runs code, or `None` if it doesn't, as far as pahole can tell: it
+    /// knows the standard library's owning pointers and collections, but not `Drop` impls.
+    fn type_drop_glue(
+        &mut self,
+        owner: &parsed::TypePath,
+        ty: &Type,
+    ) -> Result<Option<String>, Error> {
+        match ty {
+            Type::Path(x) => {
+                if let Some(path) = self.lookup(owner, x) {
+                    // Ensures the item has been processed.
+                    self.type_layout(owner, ty)?;
+                    return Ok(self.drop_glue.get(&path).cloned());
+                }
+                let name = match x.path.segments.last() {
+                    Some(segment) => segment.ident.to_string(),
+                    None => return Ok(None),
+                };
+                if name == "ManuallyDrop" || name == "MaybeUninit" || name == "PhantomData" {
+                    return Ok(None);
+                }
+                if let Some(borrowed) = cow_argument(x) {
+                    let owned = cow_owned(x, borrowed)?;
+                    return self.type_drop_glue(owner, &owned);
+                }
+                if is_heap_buffer(x) || OWNING.contains(&name.as_str()) {
+                    return Ok(Some(format!(
+                        "`{}` has drop glue",
+                        report::tokens_to_string(x)
+                    )));
+                }
+                // Other standard library types like `Option` drop what they contain.
+                for argument in generic_arguments(x, &name).unwrap_or_default() {
+                    if let Some(reason) = self.type_drop_glue(owner, argument)? {
+                        return Ok(Some(reason));
+                    }
+                }
+                Ok(None)
+            }
+            Type::Paren(x) => self.type_drop_glue(owner, &x.elem),
+            Type::Group(x) => self.type_drop_glue(owner, &x.elem),
+            Type::Tuple(x) => {
+                for elem in &x.elems {
+                    if let Some(reason) = self.type_drop_glue(owner, elem)? {
+                        return Ok(Some(reason));
+                    }
+                }
+                Ok(None)
+            }
+            Type::Array(x) if array_len(&x.len)? == 0 => Ok(None),
+            Type::Array(x) => self.type_drop_glue(owner, &x.elem),
+            _ => Ok(None),
+        }
+    }
+
+    fn fields_drop_glue(
+        &mut self,
+        owner: &parsed::TypePath,
+        fields: &[(String, Type)],
+    ) -> Result<Option<String>, Error> {
+        for (name, ty) in fields {
+            if let Some(reason) = self.type_drop_glue(owner, ty)? {
+                return Ok(Some(format!("field `{}`: {}", name, reason)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Why all-zero bytes aren't valid for the struct or variant fields `fields`.
+    fn fields_zero_invalid(
+        &mut self,
+        owner: &parsed::TypePath,
+        fields: &[(String, Type)],
+    ) -> Result<Option<String>, Error> {
+        for (name, ty) in fields {
+            if let Some(reason) = self.type_zero_invalid(owner, ty)? {
+                return Ok(Some(format!("field `{}`: {}", name, reason)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Why all-zero bytes aren't a valid value of the enum at `path` with `shape`.
+    fn enum_zero_invalid(
+        &mut self,
+        path: &parsed::TypePath,
+        shape: &layout::Shape,
+        discriminants: &[i128],
+        all_fields: &[Vec<(String, Type)>],
+    ) -> Result<Option<String>, Error> {
+        let (discriminant, variants) = match shape {
+            layout::Shape::Enum {
+                discriminant,
+                variants,
+            } => (discriminant, variants),
+            _ => unreachable!("only enums have variants"),
+        };
+        let index = match discriminant {
+            layout::Discriminant::Niche { variant, .. } => {
+                variants.iter().position(|x| x.name == *variant).unwrap()
+            }
+            _ if variants.is_empty() => return Ok(Some("there are no variants".to_string())),
+            layout::Discriminant::None => 0,
+            layout::Discriminant::Tag(_) => match discriminants.iter().position(|&x| x == 0) {
+                Some(x) => x,
+                None => return Ok(Some("no variant has discriminant 0".to_string())),
+            },
+        };
+        let reason = match self.fields_zero_invalid(path, &all_fields[index])? {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        // A zero null pointer or `NonZero` integer is the niche value of another variant.
+        if let layout::Discriminant::Niche { field, .. } = discriminant {
+            let ty = &all_fields[index]
+                .iter()
+                .find(|(x, _)| x == field)
+                .unwrap()
+                .1;
+            if is_non_null_pointer(ty) || is_non_zero(ty) {
+                return Ok(None);
+            }
+        }
+        Ok(Some(format!(
+            "variant `{}`, {}",
+            variants[index].name, reason
+        )))
+    }
+
+    fn field_layouts(
+        &mut self,
+        owner: &parsed::TypePath,
+        fields: impl IntoIterator<Item = (String, Type)>,
+    ) -> Result<Vec<(String, Type, Layout)>, Error> {
+        fields
+            .into_iter()
+            .map(|(name, ty)| {
+                let layout = self.type_layout(owner, &ty)?;
+                Ok((name, ty, layout))
+            })
+            .collect()
+    }
+
+    /// Lays out the fields of a struct or tuple with `repr`, in the order rustc would put them
+    /// in (see `StructLayout::optimized`). A `fixed_tail` field stays last.
+    fn place_fields(
+        &mut self,
+        owner: &parsed::TypePath,
+        fields: Vec<(String, Type, Layout)>,
+        repr: &parsed::Repr,
+        fixed_tail: bool,
+    ) -> Result<layout::StructLayout, Error> {
+        if !layout::reorders_fields(repr) {
+            return Ok(layout::StructLayout::with_repr(
+                Layout::new::<()>(),
+                fields,
+                repr,
+            ));
+        }
+        let sized = fields.len() - fixed_tail as usize;
+        let mut niches = Vec::with_capacity(fields.len());
+        for (_, ty, _) in &fields[..sized] {
+            niches.push(self.type_niche(owner, ty)?);
+        }
+        niches.resize(fields.len(), 0);
+        Ok(layout::StructLayout::optimized(
+            fields, &niches, repr, fixed_tail,
+        ))
+    }
+
+    fn struct_fields(s: &parsed::Struct) -> Vec<(String, Type)> {
+        match s {
+            parsed::Struct::Unit => Vec::new(),
+            parsed::Struct::Tuple(x) => x
+                .iter()
+                .enumerate()
+                .map(|(i, y)| (i.to_string(), y.clone()))
+                .collect(),
+            parsed::Struct::Struct(x) => x
+                .iter()
+                .map(|(name, ty)| (name.to_string(), ty.clone()))
+                .collect(),
+        }
+    }
+
+    /// Computes the full layout of one of the items added with `add_item`.
+    pub fn item_layout(&mut self, path: &parsed::TypePath) -> Result<layout::ItemLayout, Error> {
+        self.in_progress.insert(path.clone());
+        let result = self.compute_item_layout(path);
+        self.in_progress.remove(path);
+        result
+    }
+
+    fn compute_item_layout(
+        &mut self,
+        path: &parsed::TypePath,
+    ) -> Result<layout::ItemLayout, Error> {
+        let fields_of = |item: &parsed::Item| match item {
+            parsed::Item::Struct(x, _) => vec![Self::struct_fields(x)],
+            parsed::Item::Enum(x, _) => x.iter().map(|(_, y, _)| Self::struct_fields(y)).collect(),
+            parsed::Item::Union(x, _) => vec![x
+                .iter()
+                .map(|(name, ty)| (name.to_string(), ty.clone()))
+                .collect()],
+            parsed::Item::TypeAlias(_) | parsed::Item::Value(_) => Vec::new(),
+        };
+        let item = &self.unprocessed_items[path];
+        let all_fields = fields_of(item);
+        let drop_glue = match item {
+            // Only `ManuallyDrop` and `Copy` fields are allowed in unions.
+            parsed::Item::Union(..) | parsed::Item::Value(_) => None,
+            parsed::Item::TypeAlias(ty) => {
+                let ty = ty.clone();
+                self.type_drop_glue(path, &ty)?
+            }
+            parsed::Item::Struct(..) => self.fields_drop_glue(path, &all_fields[0])?,
+            parsed::Item::Enum(variants, _) => {
+                let names: Vec<String> = variants.iter().map(|(x, _, _)| x.to_string()).collect();
+                let mut drop_glue = None;
+                for (name, fields) in names.iter().zip(&all_fields) {
+                    if let Some(reason) = self.fields_drop_glue(path, fields)? {
+                        drop_glue = Some(format!("variant `{}`, {}", name, reason));
+                        break;
+                    }
+                }
+                drop_glue
+            }
+        };
+        let item = &self.unprocessed_items[path];
+        let (shape, total, niche, ffi_unsafe, zero_invalid) = match item {
+            parsed::Item::Struct(_, repr) => {
+                let repr = repr.clone();
+                let fields = all_fields.into_iter().next().unwrap();
+                let niche = self.max_niche(path, &fields)?;
+                let ffi_unsafe = self.fields_ffi_unsafe(path, &fields, &repr)?;
+                let zero_invalid = self.fields_zero_invalid(path, &fields)?;
+                if repr.simd {
+                    check_simd_fields(&fields)?;
+                }
+                let unsized_tail = fields
+                    .last()
+                    .is_some_and(|(_, ty)| self.is_unsized(path, ty));
+                let flexible_array = repr.c
+                    && matches!(fields.last(), Some((_, Type::Array(x))) if array_len(&x.len)? == 0);
+                let fields = self.struct_field_layouts(path, fields)?;
+                let mut layout = self.place_fields(path, fields, &repr, unsized_tail)?;
+                layout.unsized_tail = unsized_tail;
+                layout.flexible_array = flexible_array;
+                self.add_internal_padding(path, &mut layout)?;
+                check_first_offset(path, &layout.fields)?;
+                let total = layout.total;
+                (
+                    layout::Shape::Struct(layout),
+                    total,
+                    niche,
+                    ffi_unsafe,
+                    zero_invalid,
+                )
+            }
+            parsed::Item::Enum(variants, repr) if repr.c || repr.int.is_some() => {
+                let repr = repr.clone();
+                let names: Vec<syn::Ident> = variants.iter().map(|(x, _, _)| x.clone()).collect();
+                let discriminants: Vec<i128> = variants.iter().map(|(_, _, x)| *x).collect();
+                let mut ffi_unsafe = None;
+                for (name, fields) in names.iter().zip(&all_fields) {
+                    for (field, ty) in fields {
+                        if let Some(reason) = self.type_ffi_unsafe(path, ty)? {
+                            ffi_unsafe = Some(format!(
+                                "field `{}` of variant `{}` is not FFI-safe: {}",
+                                field, name, reason
+                            ));
+                            break;
+                        }
+                    }
+                    if ffi_unsafe.is_some() {
+                        break;
+                    }
+                }
+                let (shape, total, niche) =
+                    self.c_enum_layout(path, names, &discriminants, all_fields.clone(), &repr)?;
+                let zero_invalid =
+                    self.enum_zero_invalid(path, &shape, &discriminants, &all_fields)?;
+                (shape, total, niche, ffi_unsafe, zero_invalid)
+            }
+            parsed::Item::Enum(variants, _) => {
+                let names: Vec<syn::Ident> = variants.iter().map(|(x, _, _)| x.clone()).collect();
+                let discriminants: Vec<i128> = variants.iter().map(|(_, _, x)| *x).collect();
+                let (shape, total, niche) =
+                    self.enum_layout(path, names, discriminants.clone(), all_fields.clone())?;
+                let ffi_unsafe = Some(format!(
+                    "`{}` is an enum without `#[repr(C)]` or an integer `repr`",
+                    path
+                ));
+                let zero_invalid =
+                    self.enum_zero_invalid(path, &shape, &discriminants, &all_fields)?;
+                (shape, total, niche, ffi_unsafe, zero_invalid)
+            }
+            parsed::Item::Union(_, repr) => {
+                let repr = repr.clone();
+                let fields = all_fields.into_iter().next().unwrap();
+                let ffi_unsafe = self.fields_ffi_unsafe(path, &fields, &repr)?;
+                let fields = self.field_layouts(path, fields)?;
+                let (fields, total) = layout::union_fields(fields, &repr);
+                if let Some(field) = fields.iter().find(|x| x.offset != 0) {
+                    return Err(internal_error(
+                        path,
+                        &format!("union field `{}` is not at offset 0", field.name),
+                    ));
+                }
+                // Unions have no invalid values of their own.
+                (layout::Shape::Union(fields), total, 0, ffi_unsafe, None)
+            }
+            parsed::Item::TypeAlias(ty) => {
+                let ty = ty.clone();
+                let total = self.type_layout(path, &ty)?;
+                let niche = self.type_niche(path, &ty)?;
+                let ffi_unsafe = self.type_ffi_unsafe(path, &ty)?;
+                let zero_invalid = self.type_zero_invalid(path, &ty)?;
+                (
+                    layout::Shape::TypeAlias(ty),
+                    total,
+                    niche,
+                    ffi_unsafe,
+                    zero_invalid,
+                )
+            }
+            parsed::Item::Value(ty) => {
+                let ty = ty.clone();
+                let total = self.type_layout(path, &ty)?;
+                let ffi_unsafe = self.type_ffi_unsafe(path, &ty)?;
+                (layout::Shape::Value(ty), total, 0, ffi_unsafe, None)
+            }
+        };
+        let fields = match &shape {
+            layout::Shape::Struct(x) => x.fields.as_slice(),
+            layout::Shape::Union(x) => x.as_slice(),
+            _ => &[],
+        };
+        let natural = fields.iter().map(|x| x.layout.align()).max().unwrap_or(1);
+        let natural_align = match &self.unprocessed_items[path] {
+            parsed::Item::Struct(_, repr) | parsed::Item::Union(_, repr)
+                if natural != total.align() =>
+            {
+                let reason = if repr.simd {
+                    "repr(simd)"
+                } else if repr.packed.is_some() {
+                    "repr(packed)"
+                } else {
+                    "repr(align)"
+                };
+                Some((natural, reason))
+            }
+            _ => None,
+        };
+        Ok(layout::ItemLayout {
+            path: path.clone(),
+            total,
+            shape,
+            niche,
+            ffi_unsafe,
+            zero_invalid,
+            drop_glue,
+            natural_align,
+        })
+    }
+
+    fn max_niche(
+        &mut self,
+        owner: &parsed::TypePath,
+        fields: &[(String, Type)],
+    ) -> Result<u128, Error> {
+        fields
+            .iter()
+            .try_fold(0, |acc, (_, ty)| Ok(acc.max(self.type_niche(owner, ty)?)))
+    }
+
+    /// Lays out a default-repr enum, storing the discriminant in a niche of the only variant
+    /// with data when the other variants are zero-sized, and in a separate tag otherwise.
+    fn enum_layout(
+        &mut self,
+        path: &parsed::TypePath,
+        names: Vec<syn::Ident>,
+        discriminants: Vec<i128>,
+        all_fields: Vec<Vec<(String, Type)>>,
+    ) -> Result<(layout::Shape, Layout, u128), Error> {
+        let variant_count = names.len() as u128;
+        let mut sized = Vec::with_capacity(all_fields.len());
+        for fields in &all_fields {
+            let layouts = self.field_layouts(path, fields.clone())?;
+            sized.push(layouts.iter().any(|(_, _, x)| x.size() != 0));
+        }
+        let mut dataful = all_fields.iter().enumerate().filter(|&(i, _)| sized[i]);
+        if let (Some((index, fields)), None, true) =
+            (dataful.next(), dataful.next(), names.len() > 1)
+        {
+            let mut best: Option<(usize, u128)> = None;
+            for (i, (_, ty)) in fields.iter().enumerate() {
+                let niche = self.type_niche(path, ty)?;
+                if niche >= variant_count - 1 && !matches!(best, Some((_, x)) if x >= niche) {
+                    best = Some((i, niche));
+                }
+            }
+            if let Some((field, niche)) = best {
+                let mut variants = Vec::with_capacity(names.len());
+                for ((name, fields), &discriminant) in
+                    names.into_iter().zip(all_fields).zip(&discriminants)
+                {
+                    let fields = self.field_layouts(path, fields)?;
+                    let mut payload = layout::StructLayout::sequential(Layout::new::<()>(), fields);
+                    self.add_internal_padding(path, &mut payload)?;
+                    variants.push(layout::VariantLayout {
+                        name,
+                        discriminant,
+                        payload,
+                        packed_tag: false,
+                    });
+                }
+                let total = layout::enum_total(None, &variants);
+                let discriminant = layout::Discriminant::Niche {
+                    variant: variants[index].name.clone(),
+                    field: variants[index].payload.fields[field].name.clone(),
+                };
+                return Ok((
+                    layout::Shape::Enum {
+                        discriminant,
+                        variants,
+                    },
+                    total,
+                    niche - (variant_count - 1),
+                ));
+            }
+        }
+
+        let tag = layout::enum_tag(&discriminants);
+        let prefix = tag.map_or_else(Layout::new::<()>, |x| x.layout);
+        let niche = match tag {
+            Some(tag) => tag.niche(names.len()),
+            None => match all_fields.first() {
+                Some(fields) => self.max_niche(path, fields)?,
+                None => 0,
+            },
+        };
+        let mut variants = Vec::with_capacity(names.len());
+        for ((name, fields), &discriminant) in names.into_iter().zip(all_fields).zip(&discriminants)
+        {
+            let fields = self.field_layouts(path, fields)?;
+            let mut payload = layout::StructLayout::sequential(prefix, fields.clone());
+            let mut packed_tag = false;
+            // Like rustc, puts small fields first when that fits them alongside the tag, in what
+            // would otherwise be padding before a more aligned field.
+            if tag.is_some() {
+                let mut sorted = fields;
+                sorted.sort_by_key(|(_, _, x)| x.align());
+                let candidate = layout::StructLayout::sequential(prefix, sorted);
+                if candidate.total.size() < payload.total.size() {
+                    payload = candidate;
+                    packed_tag = true;
+                }
+            }
+            self.add_internal_padding(path, &mut payload)?;
+            variants.push(layout::VariantLayout {
+                name,
+                discriminant,
+                payload,
+                packed_tag,
+            });
+        }
+        let total = layout::enum_total(tag, &variants);
+        let discriminant = match tag {
+            Some(tag) => layout::Discriminant::Tag(tag),
+            None => layout::Discriminant::None,
+        };
+        Ok((
+            layout::Shape::Enum {
+                discriminant,
+                variants,
+            },
+            total,
+            niche,
+        ))
+    }
+
+    /// Lays out an enum with `repr(C)` or an integer `repr`. With only an integer `repr`, each
+    /// variant is a `repr(C)` struct starting with the tag; with `repr(C)`, the variants' fields
+    /// are in a union after the tag, which is a C `int` unless an integer `repr` is also given.
+    fn c_enum_layout(
+        &mut self,
+        path: &parsed::TypePath,
+        names: Vec<syn::Ident>,
+        discriminants: &[i128],
+        all_fields: Vec<Vec<(String, Type)>>,
+        repr: &parsed::Repr,
+    ) -> Result<(layout::Shape, Layout, u128), Error> {
+        let pointer_width = self.target.pointer_width;
+        let tag = match &repr.int {
+            Some(int) => layout::int_tag(int, pointer_width).unwrap(),
+            None => layout::int_tag(&parse_quote!(i32), pointer_width).unwrap(),
+        };
+        let mut all_layouts = Vec::with_capacity(all_fields.len());
+        for fields in all_fields {
+            all_layouts.push(self.field_layouts(path, fields)?);
+        }
+        // The union of the variants' fields starts at its alignment, after the tag.
+        let prefix = if repr.c {
+            let align = all_layouts
+                .iter()
+                .flatten()
+                .map(|(_, _, x)| x.align())
+                .max()
+                .unwrap_or(1);
+            let offset = tag.layout.size().div_ceil(align) * align;
+            Layout::from_size_align(offset, tag.layout.align()).unwrap()
+        } else {
+            tag.layout
+        };
+        let mut variants = Vec::with_capacity(names.len());
+        for ((name, fields), &discriminant) in names.into_iter().zip(all_layouts).zip(discriminants)
+        {
+            let has_fields = !fields.is_empty();
+            let mut payload = layout::StructLayout::sequential(prefix, fields);
+            if has_fields && prefix.size() > tag.layout.size() {
+                payload.holes.insert(
+                    0,
+                    layout::Hole {
+                        offset: tag.layout.size(),
+                        size: prefix.size() - tag.layout.size(),
+                    },
+                );
+            }
+            self.add_internal_padding(path, &mut payload)?;
+            variants.push(layout::VariantLayout {
+                name,
+                discriminant,
+                payload,
+                packed_tag: false,
+            });
+        }
+        let total = layout::enum_total(Some(tag), &variants);
+        let niche = tag.niche(variants.len());
+        Ok((
+            layout::Shape::Enum {
+                discriminant: layout::Discriminant::Tag(tag),
+                variants,
+            },
+            total,
+            niche,
+        ))
+    }
+
+    /// Which added items each item embeds, directly or through `Option`, arrays, or tuples, but not
+    /// behind pointers. Only items whose layouts have been computed are included.
+    pub fn dependency_graph(&self) -> &HashMap<parsed::TypePath, Vec<parsed::TypePath>> {
+        &self.dependencies
+    }
+
+    /// Whether `path` is a struct whose layout is guaranteed by its `repr`.
+    fn has_stable_layout(&self, path: &parsed::TypePath) -> bool {
+        matches!(
+            self.unprocessed_items.get(path),
+            Some(parsed::Item::Struct(_, repr)) if repr.c || repr.transparent
+        )
+    }
+
+    /// Computes the layouts of all added items, in declaration order.
+    pub fn resolve(&mut self) -> Result<Vec<layout::ItemLayout>, Error> {
+        self.resolve_where(|_| true)
+    }
+
+    /// Computes the layouts of the added items whose paths end with one of `names`, along with
+    /// the types they depend on, returning them and the names that matched nothing.
+    pub fn resolve_only<'a>(
+        &mut self,
+        names: &'a [syn::Path],
+    ) -> Result<(Vec<layout::ItemLayout>, Vec<&'a syn::Path>), Error> {
+        let matches = |path: &parsed::TypePath, name: &syn::Path| {
+            let path = &path.0.path.segments;
+            let name = &name.segments;
+            name.len() <= path.len()
+                && path
+                    .iter()
+                    .skip(path.len() - name.len())
+                    .zip(name)
+                    .all(|(x, y)| x.ident == y.ident)
+        };
+        let missing = names
+            .iter()
+            .filter(|name| !self.item_order.iter().any(|path| matches(path, name)))
+            .collect();
+        let layouts = self.resolve_where(|path| names.iter().any(|name| matches(path, name)))?;
+        Ok((layouts, missing))
+    }
+
+    fn resolve_where(
+        &mut self,
+        keep: impl Fn(&parsed::TypePath) -> bool,
+    ) -> Result<Vec<layout::ItemLayout>, Error> {
+        let mut layouts = Vec::with_capacity(self.item_order.len());
+        for path in self.item_order.clone() {
+            if !keep(&path) {
+                continue;
+            }
+            let layout = self.item_layout(&path)?;
+            // Values live in a different namespace, so must not be found by `lookup`.
+            if !matches!(layout.shape, layout::Shape::Value(_)) {
+                self.record(&layout);
+            }
+            layouts.push(layout);
+        }
+        Ok(layouts)
+    }
+}
+
+/// A `layout_of_<path>!` macro answering `(size)`, `(align)`, and, for structs and unions,
+/// `(offset field)` and `(size field)` with the numbers from `layout`, e.g. `layout_of_m_A!(offset
+/// b)` for field `b` of `m::A`. Generic instantiations get none.
+fn layout_macro(layout: &layout::ItemLayout) -> Option<proc_macro2::TokenStream> {
+    let segments = &layout.path.0.path.segments;
+    if segments.iter().any(|x| !x.arguments.is_empty()) {
+        return None;
+    }
+    let names: Vec<String> = segments.iter().map(|x| x.ident.to_string()).collect();
+    let name = quote::format_ident!("layout_of_{}", names.join("_"));
+    let fields = match &layout.shape {
+        layout::Shape::Struct(x) => x.fields.as_slice(),
+        layout::Shape::Union(x) => x.as_slice(),
+        _ => &[],
+    };
+    let arms = fields.iter().map(|x| {
+        let field: proc_macro2::TokenStream = x.name.parse().unwrap();
+        let (offset, size) = (x.offset, x.layout.size());
+        quote::quote! {
+            (offset #field) => { #offset };
+            (size #field) => { #size };
+        }
+    });
+    let (size, align) = (layout.total.size(), layout.total.align());
+    Some(quote::quote! {
+        #[allow(unused_macros)]
+        macro_rules! #name {
+            (size) => { #size };
+            (align) => { #align };
+            #(#arms)*
+        }
+    })
+}
+
+/// A `<path>_layout` module with `SIZE` and `ALIGN` consts, and, for structs and unions,
+/// `offsets` and `sizes` modules with a const per field, e.g. `m_A_layout::offsets::b` for field
+/// `b` of `m::A`. Tuple fields are named like `_0`. Generic instantiations get none.
+fn layout_mod(layout: &layout::ItemLayout) -> Option<proc_macro2::TokenStream> {
+    let segments = &layout.path.0.path.segments;
+    if segments.iter().any(|x| !x.arguments.is_empty()) {
+        return None;
+    }
+    let names: Vec<String> = segments.iter().map(|x| x.ident.to_string()).collect();
+    let name = quote::format_ident!("{}_layout", names.join("_"));
+    let fields = match &layout.shape {
+        layout::Shape::Struct(x) => x.fields.as_slice(),
+        layout::Shape::Union(x) => x.as_slice(),
+        _ => &[],
+    };
+    let idents: Vec<syn::Ident> = fields
+        .iter()
+        .map(|x| match x.name.parse::<usize>() {
+            Ok(index) => quote::format_ident!("_{}", index),
+            Err(_) => quote::format_ident!("{}", x.name),
+        })
+        .collect();
+    let offsets = fields.iter().map(|x| x.offset);
+    let sizes = fields.iter().map(|x| x.layout.size());
+    let (size, align) = (layout.total.size(), layout.total.align());
+    Some(quote::quote! {
+        #[allow(dead_code, non_snake_case, non_upper_case_globals)]
+        mod #name {
+            pub const SIZE: usize = #size;
+            pub const ALIGN: usize = #align;
+            pub mod offsets {
+                #(pub const #idents: usize = #offsets;)*
+            }
+            pub mod sizes {
+                #(pub const #idents: usize = #sizes;)*
+            }
+        }
+    })
+}
+
+/// Prepends the layout summary to the doc comments of `item` and the items inside it, skipping
+/// generic items.
+fn add_layout_docs(
+    item: &mut Item,
+    parent_path: &parsed::TypePath,
+    layouts: &HashMap<&parsed::TypePath, &layout::ItemLayout>,
+) {
+    let (ident, attrs, generics) = match item {
+        Item::Mod(x) => {
+            let mut path = parent_path.clone();
+            path.push(x.ident.clone().into());
+            if let Some((_, items)) = &mut x.content {
+                for item in items {
+                    add_layout_docs(item, &path, layouts);
+                }
+            }
+            return;
+        }
+        Item::Struct(x) => (&x.ident, &mut x.attrs, &x.generics),
+        Item::Enum(x) => (&x.ident, &mut x.attrs, &x.generics),
+        Item::Union(x) => (&x.ident, &mut x.attrs, &x.generics),
+        Item::Type(x) => (&x.ident, &mut x.attrs, &x.generics),
+        _ => return,
+    };
+    if !generics.params.is_empty() {
+        return;
+    }
+    let mut path = parent_path.clone();
+    path.push(ident.clone().into());
+    if let Some(layout) = layouts.get(&path) {
+        let summary = format!(" {}", report::doc_summary(layout));
+        let has_docs = attrs.iter().any(|x| x.path.is_ident("doc"));
+        let mut docs: Vec<syn::Attribute> = vec![parse_quote!(#[doc = #summary])];
+        if has_docs {
+            docs.push(parse_quote!(#[doc = ""]));
+        }
+        attrs.splice(0..0, docs);
+    }
+}
+
+/// Adds an error to `errors` for each item in `item` that isn't FFI-safe, pointing at the field
+/// making it so, if there's one.
+fn check_ffi_safe(
+    item: &Item,
+    parent_path: &parsed::TypePath,
+    layouts: &HashMap<&parsed::TypePath, &layout::ItemLayout>,
+    errors: &mut Option<Error>,
+) {
+    let (ident, generics) = match item {
+        Item::Mod(x) => {
+            let mut path = parent_path.clone();
+            path.push(x.ident.clone().into());
+            for item in x.content.iter().flat_map(|(_, items)| items) {
+                check_ffi_safe(item, &path, layouts, errors);
+            }
+            return;
+        }
+        Item::Struct(x) => (&x.ident, &x.generics),
+        Item::Enum(x) => (&x.ident, &x.generics),
+        Item::Union(x) => (&x.ident, &x.generics),
+        Item::Type(x) => (&x.ident, &x.generics),
+        _ => return,
+    };
+    if !generics.params.is_empty() {
+        return;
+    }
+    let mut path = parent_path.clone();
+    path.push(ident.clone().into());
+    let reason = match layouts.get(&path).and_then(|x| x.ffi_unsafe.as_ref()) {
+        Some(x) => x,
+        None => return,
+    };
+    // The reason starts with the field, e.g. "field `x` of variant `A` is not FFI-safe: ...".
+    let field_name = reason
+        .strip_prefix("field `")
+        .and_then(|x| x.split('`').next());
+    let variant_name = reason
+        .strip_prefix("field `")
+        .and_then(|x| x.split("` of variant `").nth(1))
+        .and_then(|x| x.split('`').next());
+    let fields: Vec<&syn::Field> = match item {
+        Item::Struct(x) => x.fields.iter().collect(),
+        Item::Union(x) => x.fields.named.iter().collect(),
+        Item::Enum(x) => x
+            .variants
+            .iter()
+            .filter(|y| variant_name.is_some_and(|name| y.ident == name))
+            .flat_map(|y| &y.fields)
+            .collect(),
+        _ => Vec::new(),
+    };
+    let field = fields.iter().enumerate().find(|(i, x)| match &x.ident {
+        Some(ident) => field_name.is_some_and(|name| ident == name),
+        None => field_name == Some(i.to_string().as_str()),
+    });
+    let message = format!("`{}` is not FFI-safe: {}", path, reason);
+    let error = match field {
+        Some((_, x)) => Error::new_spanned(x, message),
+        None => Error::new_spanned(ident, message),
+    };
+    match errors {
+        Some(x) => x.combine(error),
+        None => *errors = Some(error),
+    }
+}
+
+/// A `#[pahole_hole(N)]` on a field, asserting that an `N`-byte hole precedes it.
+struct ExpectedHole {
+    path: parsed::TypePath,
+    field: String,
+    size: syn::LitInt,
+}
+
+/// Removes the `pahole_hole` attributes from the fields of the structs in `item`, which rustc
+/// wouldn't recognize, and returns what they assert.
+fn take_expected_holes(
+    item: &mut Item,
+    parent_path: &parsed::TypePath,
+) -> Result<Vec<ExpectedHole>, Error> {
+    let mut holes = Vec::new();
+    match item {
+        Item::Mod(x) => {
+            let mut path = parent_path.clone();
+            path.push(x.ident.clone().into());
+            if let Some((_, items)) = &mut x.content {
+                for item in items {
+                    holes.extend(take_expected_holes(item, &path)?);
+                }
+            }
+        }
+        Item::Struct(x) => {
+            let mut path = parent_path.clone();
+            path.push(x.ident.clone().into());
+            for (i, field) in x.fields.iter_mut().enumerate() {
+                let name = match &field.ident {
+                    Some(ident) => ident.to_string(),
+                    None => i.to_string(),
+                };
+                let mut error = None;
+                field.attrs.retain(|attr| {
+                    if !attr.path.is_ident("pahole_hole") {
+                        return true;
+                    }
+                    match attr.parse_args::<syn::LitInt>() {
+                        Ok(size) => holes.push(ExpectedHole {
+                            path: path.clone(),
+                            field: name.clone(),
+                            size,
+                        }),
+                        Err(_) => {
+                            error = Some(Error::new_spanned(
+                                attr,
+                                "expected `#[pahole_hole(N)]`, with the hole's size in bytes",
+                            ))
+                        }
+                    }
+                    false
+                });
+                if let Some(error) = error {
+                    return Err(error);
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(holes)
+}
+
+fn check_expected_holes(
+    holes: &[ExpectedHole],
+    layouts: &[layout::ItemLayout],
+) -> Result<(), Error> {
+    let mut errors: Option<Error> = None;
+    for expected in holes {
+        let fields = match layouts.iter().find(|x| x.path == expected.path) {
+            Some(layout::ItemLayout {
+                shape: layout::Shape::Struct(x),
+                ..
+            }) => x,
+            _ => continue,
+        };
+        let field = match fields.fields.iter().find(|x| x.name == expected.field) {
+            Some(x) => x,
+            None => continue,
+        };
+        let actual = fields
+            .holes
+            .iter()
+            .find(|x| x.offset + x.size == field.offset)
+            .map_or(0, |x| x.size);
+        let size: usize = expected.size.base10_parse()?;
+        if size != actual {
+            let found = match actual {
+                0 => "there is no hole".to_string(),
+                n => format!("the hole is {} bytes", n),
+            };
+            let error = Error::new_spanned(
+                &expected.size,
+                format!(
+                    "expected a {}-byte hole before `{}`, but {}",
+                    size, expected.field, found
+                ),
+            );
+            match &mut errors {
+                Some(x) => x.combine(error),
+                None => errors = Some(error),
+            }
+        }
+    }
+    match errors {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
+
+/// Checks the annotated item against the `size` and `align` options.
+fn check_expected_layout(
+    options: &options::Options,
+    item: &Item,
+    layouts: &[layout::ItemLayout],
+) -> Result<(), Error> {
+    let ident = match item {
+        Item::Struct(x) => &x.ident,
+        Item::Enum(x) => &x.ident,
+        Item::Union(x) => &x.ident,
+        Item::Type(x) => &x.ident,
+        _ => {
+            return match options.size.as_ref().or(options.align.as_ref()) {
+                Some(lit) => Err(Error::new_spanned(
+                    lit,
+                    "pahole can only check the size and alignment of a single struct, enum, \
+                     union, or type alias",
+                )),
+                None => Ok(()),
+            }
+        }
+    };
+    let layout = match layouts.iter().find(|x| x.path.0.path.is_ident(ident)) {
+        Some(x) => x,
+        None => return Ok(()),
+    };
+    let checks = [
+        (&options.size, layout.total.size(), ""),
+        (&options.align, layout.total.align(), "aligned to "),
+    ];
+    let mut errors: Option<Error> = None;
+    for (lit, actual, what) in &checks {
+        let lit = match lit {
+            Some(x) => x,
+            None => continue,
+        };
+        let expected: usize = lit.base10_parse()?;
+        if expected != *actual {
+            let error = Error::new_spanned(
+                lit,
+                format!(
+                    "expected `{}` to be {}{} bytes, but it is {}{} bytes",
+                    ident, what, expected, what, actual
+                ),
+            );
+            match &mut errors {
+                Some(x) => x.combine(error),
+                None => errors = Some(error),
+            }
+        }
+    }
+    match errors {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
+
+/// Checks the structs named in a C layout file against their layouts, erroring with every
+/// difference. Each line gives a struct's size and alignment, or a field's offset and size, e.g.
+/// `Header = { size = 8, align = 4 }` and `"Header.len" = { offset = 0, size = 4 }`.
+fn check_c_layout(
+    file: &LitStr,
+    entries: &[sizes::Entry],
+    layouts: &[layout::ItemLayout],
+) -> Result<(), Error> {
+    let value = |entry: &sizes::Entry, key: &str| {
+        entry
+            .fields
+            .iter()
+            .find(|(x, _)| x == key)
+            .map(|(_, y)| *y as usize)
+    };
+    let mut structs: Vec<&str> = Vec::new();
+    for entry in entries {
+        let name = entry.path.split('.').next().unwrap();
+        if !structs.contains(&name) {
+            structs.push(name);
+        }
+    }
+    let mut diffs = String::new();
+    for name in structs {
+        let layout = layouts.iter().find(|x| x.path.to_string() == name);
+        let fields = match layout.map(|x| &x.shape) {
+            Some(layout::Shape::Struct(x)) => &x.fields,
+            _ => {
+                writeln!(diffs, "`{}` is not an analyzed struct", name).unwrap();
+                continue;
+            }
+        };
+        let layout = layout.unwrap();
+        let mut lines = Vec::new();
+        let mut in_c = Vec::new();
+        for entry in entries {
+            if entry.path == name {
+                for (key, actual) in &[
+                    ("size", layout.total.size()),
+                    ("align", layout.total.align()),
+                ] {
+                    match value(entry, key) {
+                        Some(expected) if expected != *actual => lines.push(format!(
+                            "{}: {} in Rust, but {} in C",
+                            key, actual, expected
+                        )),
+                        _ => {}
+                    }
+                }
+                continue;
+            }
+            let field = match entry
+                .path
+                .strip_prefix(name)
+                .and_then(|x| x.strip_prefix('.'))
+            {
+                Some(x) => x,
+                None => continue,
+            };
+            in_c.push(field);
+            let expected = (value(entry, "offset"), value(entry, "size"));
+            match fields.iter().find(|x| x.name == field) {
+                Some(x) if expected != (Some(x.offset), Some(x.layout.size())) => {
+                    let show = |x: Option<usize>| x.map_or("?".to_string(), |y| y.to_string());
+                    lines.push(format!(
+                        "field `{}`: offset {}, size {} in Rust, but offset {}, size {} in C",
+                        field,
+                        x.offset,
+                        x.layout.size(),
+                        show(expected.0),
+                        show(expected.1)
+                    ));
+                }
+                Some(_) => {}
+                None => lines.push(format!("field `{}`: only in C", field)),
+            }
+        }
+        for field in fields.iter().filter(|x| !in_c.contains(&x.name.as_str())) {
+            lines.push(format!("field `{}`: only in Rust", field.name));
+        }
+        if !lines.is_empty() {
+            writeln!(diffs, "`{}` doesn't match its C layout:", name).unwrap();
+            for line in lines {
+                writeln!(diffs, "  {}", line).unwrap();
+            }
+        }
+    }
+    if diffs.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::new_spanned(file, diffs.trim_end()))
+    }
+}
+
+/// The internal representation of the analyzed items, for diagnosing resolution problems, if the
+/// `debug_parse` option asks for it.
+fn debug_parse(options: &options::Options, data: &Data) -> String {
+    if options.debug_parse {
+        format!("{:#?}\n", data)
+    } else {
+        String::new()
+    }
+}
+
+/// `items` without those that have no layout, like `fn`s and `impl`s, recursing into inline `mod`s.
+fn items_with_layouts(items: &[Item]) -> Vec<Item> {
+    items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Mod(x) => {
+                let mut x = x.clone();
+                if let Some((_, items)) = &mut x.content {
+                    *items = items_with_layouts(items);
+                }
+                Some(Item::Mod(x))
+            }
+            Item::Fn(_)
+            | Item::Impl(_)
+            | Item::Trait(_)
+            | Item::TraitAlias(_)
+            | Item::Macro(_)
+            | Item::Macro2(_)
+            | Item::ExternCrate(_) => None,
+            _ => Some(item.clone()),
+        })
+        .collect()
+}
+
+/// Lays out the items of a whole file for `target`, or the host if `None`, with `features` enabled
+/// if they're known. This is what the `pahole` command reports.
+pub fn analyze_syn_file(
+    file: &syn::File,
+    target: Option<layout::Target>,
+    features: Option<HashSet<String>>,
+) -> Result<Vec<layout::ItemLayout>, Error> {
+    let mut data = Data::new();
+    if let Some(target) = target {
+        data.set_target(target);
+        data.clear_processed();
+    }
+    if let Some(features) = features {
+        data.set_features(features);
+    }
+    for item in items_with_layouts(&file.items) {
+        data.add_item(parsed::TypePath::new(), item)?;
+    }
+    data.resolve()
+}
+
+/// Analyzes `item` and prints the report, returning the tokens to emit instead of the item if it
+/// was changed or the options add items next to it.
+fn run(args: AttributeArgs, mut item: Item) -> Result<Option<proc_macro2::TokenStream>, Error> {
+    let options = options::Options::parse(args)?;
+    let as_repr = match &options.as_repr {
+        Some(lit) => {
+            let tokens: proc_macro2::TokenStream = lit.parse()?;
+            Some(parse_struct_repr(&[parse_quote!(#[repr(#tokens)])])?)
+        }
+        None => None,
+    };
+    let mut data = if options.no_builtins {
+        Data::without_builtins()
+    } else {
+        Data::new()
+    };
+    if let Some(pointer_width) = options.pointer_width {
+        data.set_target(layout::Target { pointer_width });
+        data.clear_processed();
+    }
+    if let Some(file) = &options.sizes {
+        for (path, registered) in sizes::load(file)? {
+            match registered {
+                sizes::Registered::Layout(layout, niche) => {
+                    data.add_layout(path.clone(), layout);
+                    if niche != 0 {
+                        data.register_niche(path, niche);
+                    }
+                }
+                sizes::Registered::Niche(niche) => data.register_niche(path, niche),
+                sizes::Registered::Number(value) => data.add_type_number(path, value),
+            }
+        }
+    }
+    if let Some(unknown) = options.unknown {
+        data.set_unknown_types(unknown);
+    }
+    if let Some(features) = &options.features {
+        data.set_features(features.iter().cloned().collect());
+    }
+    let holes = take_expected_holes(&mut item, &parsed::TypePath::new())?;
+    data.add_item(parsed::TypePath::new(), item.clone())?;
+    let mut layouts = match &options.only {
+        Some(names) => {
+            let (layouts, missing) = data.resolve_only(names)?;
+            for name in missing {
+                eprintln!(
+                    "warning: pahole found no item named `{}`",
+                    report::tokens_to_string(name)
+                );
+            }
+            layouts
+        }
+        None => data.resolve()?,
+    };
+    for ty in &options.monomorphize {
+        layouts.push(data.monomorphize(ty)?);
+    }
+    for name in &data.assumed_types {
+        eprintln!(
+            "warning: pahole could not find the type `{}`, so it assumed a layout for it",
+            name
+        );
+    }
+    if let Some(order) = options.sort {
+        report::sort(&mut layouts, order);
+    }
+    eprint!("{}", debug_parse(&options, &data));
+    if !options.keep_aliases {
+        for layout in &mut layouts {
+            data.expand_aliases(layout);
+        }
+    }
+    for layout in &layouts {
+        if options.format == options::Format::Compact {
+            eprint!("{}", report::compact(layout));
+            continue;
+        }
+        eprint!("{}", report::header(layout, options.label.as_deref()));
+        eprint!("{}", report::table(layout));
+        if let Some(instance) = data.alias_instance(layout)? {
+            eprint!("{}", report::table(&instance));
+        }
+        if let Some(label) = data.default_instantiations.get(&layout.path) {
+            eprintln!("laid out as the default instantiation `{}`", label);
+        }
+        eprint!("{}", report::hot_prefix(layout));
+        eprint!("{}", report::array_waste(layout));
+        eprint!(
+            "{}",
+            report::boxed_fields(layout, &data.boxed_fields(layout)?)
+        );
+        eprint!(
+            "{}",
+            report::union_fields(layout, &data.union_fields(layout)?)
+        );
+        // rustc already picks the field order for the default representation, so moving fields
+        // around only helps when they are laid out in declaration order.
+        let declaration_order = match data.unprocessed_items.get(&layout.path) {
+            Some(parsed::Item::Struct(_, repr)) => !layout::reorders_fields(repr),
+            _ => false,
+        };
+        if let Some(parsed::Item::Struct(_, repr)) = data.unprocessed_items.get(&layout.path) {
+            if declaration_order {
+                eprint!("{}", report::hole_fillings(layout));
+            }
+            if repr.c {
+                eprint!("{}", report::bitfield_note(layout));
+                eprint!("{}", report::bool_note(layout));
+            }
+            match repr.packed {
+                Some(pack) => eprint!("{}", report::packed_references(layout, pack)),
+                None if declaration_order => eprint!("{}", report::ascending_alignment(layout)),
+                None => {}
+            }
+        }
+        if options.bytemap {
+            eprint!("{}", report::bytemap(layout));
+        }
+        if let Some(bits) = options.flag_bits {
+            eprint!("{}", report::flag_bits(layout, bits));
+        }
+        if options.field_percentages {
+            eprint!("{}", report::field_percentages(layout));
+        }
+        if options.show_packed_baseline {
+            eprint!("{}", report::packed_baseline(layout));
+        }
+        if let Some(count) = options.count {
+            eprint!("{}", report::instance_totals(layout, count));
+        }
+        if let Some(count) = options.aos_soa {
+            eprint!("{}", report::aos_soa(layout, count));
+        }
+        if let (Some(repr), Some(lit)) = (&as_repr, &options.as_repr) {
+            if data.struct_definitions.contains_key(&layout.path) {
+                let hypothetical = data.layout_with_repr(&layout.path, repr.clone())?;
+                eprintln!(
+                    "hypothetically, with `#[repr({})]` (size {} -> {}):",
+                    lit.value(),
+                    layout.total.size(),
+                    hypothetical.total.size()
+                );
+                eprint!("{}", report::table(&hypothetical));
+            }
+        }
+        if let Some(definition) = data.struct_definitions.get(&layout.path) {
+            if declaration_order {
+                eprint!("{}", report::reordering(layout, definition));
+                eprint!("{}", report::small_field_grouping(layout));
+            }
+        }
+    }
+    if options.symbolic_ptr {
+        let by_width = data.resolve_for_each_pointer_width()?;
+        for layout in &layouts {
+            let variants: Vec<_> = by_width
+                .iter()
+                .filter_map(|x| x.iter().find(|y| y.path == layout.path))
+                .collect();
+            if variants.len() != by_width.len() {
+                continue;
+            }
+            if options.format == options::Format::Compact {
+                eprint!("{}", report::compact_target_diff(&variants));
+            } else {
+                eprint!("{}", report::symbolic(&variants));
+            }
+        }
+    }
+    for query in &options.what_if {
+        let path = parsed::TypePath(query.path.clone());
+        let (change, delta) = match &query.added {
+            Some(ty) => (
+                format!(
+                    "adding `{}: {}` to `{}`",
+                    query.name,
+                    report::tokens_to_string(ty),
+                    path
+                ),
+                data.what_if_field_added(&path, &query.name, ty.clone()),
+            ),
+            None => (
+                format!("removing `{}` from `{}`", query.name, path),
+                data.what_if_field_removed(&path, &query.name),
+            ),
+        };
+        let delta = delta.map_err(|err| Error::new_spanned(&query.lit, err))?;
+        eprint!("{}", report::what_if(&change, &delta));
+    }
+    eprint!("{}", report::std_layouts_note(&data.std_layouts_used));
+    if options.histogram {
+        eprint!("{}", report::histogram(&layouts));
+    }
+    if options.layout_compatible {
+        eprint!(
+            "{}",
+            report::layout_compatible(&layouts, |x| data.has_stable_layout(&x.path))
+        );
+    }
+    if options.dependency_graph {
+        eprint!(
+            "{}",
+            report::dependency_graph(&layouts, data.dependency_graph())
+        );
+    }
+    check_expected_layout(&options, &item, &layouts)?;
+    check_expected_holes(&holes, &layouts)?;
+    if options.require_ffi_safe {
+        let by_path = layouts.iter().map(|x| (&x.path, x)).collect();
+        let mut errors = None;
+        check_ffi_safe(&item, &parsed::TypePath::new(), &by_path, &mut errors);
+        if let Some(error) = errors {
+            return Err(error);
+        }
+    }
+    if let Some(file) = &options.c_layout {
+        let (_, entries) = sizes::read(file)?;
+        check_c_layout(file, &entries, &layouts)?;
+    }
+    #[cfg(feature = "serde")]
+    {
+        if options.emit_json {
+            eprintln!("{}", golden::to_json(&layouts));
+        }
+        if let Some(file) = &options.golden {
+            golden::check(file, &layouts)?;
+        }
+    }
+    if options.emit_docs {
+        let by_path = layouts.iter().map(|x| (&x.path, x)).collect();
+        add_layout_docs(&mut item, &parsed::TypePath::new(), &by_path);
+    }
+    let mut generated = proc_macro2::TokenStream::new();
+    if options.emit_table_macro {
+        generated.extend(layouts.iter().filter_map(layout_macro));
+    }
+    if options.emit_mod {
+        generated.extend(layouts.iter().filter_map(layout_mod));
+    }
+    if options.emit_docs || !holes.is_empty() || !generated.is_empty() {
+        return Ok(Some(quote::quote!(#item #generated)));
+    }
+    Ok(None)
+}
+
+/// The expansion of `#[pahole]` with `args` on `item`, or `None` to leave the item as written.
+pub fn expand(args: AttributeArgs, item: Item) -> Option<proc_macro2::TokenStream> {
+    match run(args, item.clone()) {
+        Ok(tokens) => tokens,
+        Err(err) => {
+            // Keeping the item avoids follow-up errors wherever it's used, so that the only error
+            // is the one pointing at the problem. That includes rustc rejecting `pahole_hole`.
+            let mut item = item;
+            let _ = take_expected_holes(&mut item, &parsed::TypePath::new());
+            let err = err.to_compile_error();
+            Some(quote::quote!(#err #item))
+        }
+    }
+}
+
+/// The report for an ad-hoc type, laid out as the alias `Type`, which `pahole_type!` prints.
+pub fn type_report(ty: &Type) -> Result<String, Error> {
+    let mut data = Data::new();
+    data.add_item(parsed::TypePath::new(), parse_quote!(type Type = #ty;))?;
+    let layout = data.resolve()?.remove(0);
+    let label = report::tokens_to_string(ty);
+    Ok(report::header(&layout, Some(&label)) + &report::table(&layout))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn try_analyze(item: Item) -> Result<Vec<layout::ItemLayout>, Error> {
+        let mut data = Data::new();
+        data.add_item(parsed::TypePath::new(), item)?;
+        data.resolve()
+    }
+
+    fn analyze(item: Item) -> Vec<layout::ItemLayout> {
+        try_analyze(item).unwrap()
+    }
+
+    /// Like `analyze`, but keyed by the items' paths, e.g. `"a::b::C"`.
+    fn analyze_by_path(item: Item) -> HashMap<String, layout::ItemLayout> {
+        analyze(item)
+            .into_iter()
+            .map(|x| (x.path.to_string(), x))
+            .collect()
+    }
+
+    #[test]
+    fn histogram_buckets_sizes() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                struct Small(u8);
+                struct Word(u64);
+                struct Pair(u64, u64);
+                struct Big([u64; 8]);
+            }
+        });
+        let histogram = report::histogram(&layouts);
+        assert!(
+            histogram.contains("         0-8     2  ##\n"),
+            "{}",
+            histogram
+        );
+        assert!(
+            histogram.contains("        9-16     1  #\n"),
+            "{}",
+            histogram
+        );
+        assert!(
+            histogram.contains("       33-64     1  #\n"),
+            "{}",
+            histogram
+        );
+        let largest = histogram.split("largest types:\n").nth(1).unwrap();
+        assert!(
+            largest.starts_with("      64  m::Big\n      16  m::Pair\n"),
+            "{}",
+            largest
+        );
+    }
+
+    #[test]
+    fn boxed_self_is_a_pointer() {
+        let layouts = analyze(parse_quote! {
+            struct Node {
+                val: u32,
+                next: Option<Box<Self>>,
+                children: Box<[Self]>,
+            }
+        });
+        let ptr = std::mem::size_of::<usize>();
+        assert_eq!(layouts[0].total.size(), 4 * ptr);
+        assert_eq!(layouts[0].total.align(), ptr);
+    }
+
+    #[test]
+    fn bare_self_has_infinite_size() {
+        let err = try_analyze(parse_quote! {
+            enum List {
+                Nil,
+                Cons(u32, Self),
+            }
+        })
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`List` contains itself and would have an infinite size"
+        );
+    }
+
+    #[test]
+    fn discriminant_stored_in_niche() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                enum E { A, B(NonZeroU32) }
+                enum Flag { Unknown, Known(u8, bool) }
+                enum Tagged { A, B(u32) }
+                struct S { a: Option<NonZeroU32>, b: Option<Option<bool>> }
+            }
+        });
+        assert_eq!(layouts[0].total, Layout::new::<u32>());
+        assert!(report::table(&layouts[0])
+            .contains("discriminant stored in niche of field `0` of variant `B`\n"));
+        assert_eq!(layouts[1].total.size(), 2);
+        assert_eq!(layouts[2].total.size(), 8);
+        assert!(!report::table(&layouts[2]).contains("niche"));
+        assert_eq!(layouts[3].total.size(), 8);
+    }
+
+    #[test]
+    fn sizes_file_parsing() {
+        let entries = sizes::parse(
+            "# opaque C types\n\
+             \n\
+             \"libc::timespec\" = { size = 16, align = 8 }\n\
+             Handle = {size=4,align=4} # trailing comment\n",
+        )
+        .unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                sizes::Entry {
+                    path: "libc::timespec".to_string(),
+                    fields: vec![("size".to_string(), 16), ("align".to_string(), 8)],
+                },
+                sizes::Entry {
+                    path: "Handle".to_string(),
+                    fields: vec![("size".to_string(), 4), ("align".to_string(), 4)],
+                },
+            ]
+        );
+        assert_eq!(
+            sizes::parse("a = { size = 1 }\nb = { size = 1 align = 1 }").unwrap_err(),
+            "line 2: expected `,`"
+        );
+    }
+
+    #[test]
+    fn registered_layouts_resolve() {
+        let mut data = Data::new();
+        data.add_layout(
+            parsed::TypePath(parse_quote!(libc::timespec)),
+            Layout::new::<[u64; 2]>(),
+        );
+        data.add_item(
+            parsed::TypePath::new(),
+            parse_quote! {
+                mod m {
+                    struct Timer { deadline: libc::timespec, armed: bool }
+                }
+            },
+        )
+        .unwrap();
+        let layouts = data.resolve().unwrap();
+        assert_eq!(layouts[0].total.size(), 24);
+    }
+
+    #[test]
+    fn const_and_static_report_their_type() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                struct Point { x: u16, y: u16 }
+                const ORIGIN: Point = Point { x: 0, y: 0 };
+                static TABLE: [(u8, u32); 4] = [(0, 0); 4];
+            }
+        });
+        assert_eq!(layouts[1].total, Layout::new::<[u16; 2]>());
+        assert_eq!(layouts[2].total, Layout::new::<[(u8, u32); 4]>());
+        assert_eq!(
+            report::table(&layouts[2]),
+            "m::TABLE: size 32, align 4\n  : [(u8, u32); 4]\n"
+        );
+    }
+
+    #[test]
+    fn values_are_not_types() {
+        let err = try_analyze(parse_quote! {
+            mod m {
+                const N: u8 = 1;
+                struct S(N);
+            }
+        })
+        .unwrap_err();
+        assert_eq!(err.to_string(), "pahole could not find the type `N`");
+    }
+
+    #[test]
+    fn reordering_snippet_keeps_attributes() {
+        let definition: syn::ItemStruct = parse_quote! {
+            #[derive(Debug)]
+            pub struct S<T> where T: Copy {
+                pub(crate) a: u8,
+                /// The big one.
+                #[allow(dead_code)]
+                b: u64,
+                c: Option<T>,
+            }
+        };
+        assert_eq!(
+            report::struct_snippet(&definition, &[1, 0, 2]),
+            "#[derive(Debug)]\n\
+             pub struct S<T> where T: Copy {\n\
+             \x20   #[doc = r\" The big one.\"]\n\
+             \x20   #[allow(dead_code)]\n\
+             \x20   b: u64,\n\
+             \x20   pub(crate) a: u8,\n\
+             \x20   c: Option<T>,\n\
+             }\n"
+        );
+
+        let item: syn::ItemStruct = parse_quote!(
+            #[repr(C)]
+            struct Tuple(pub u8, u32, u8);
+        );
+        let layouts = analyze(Item::Struct(item.clone()));
+        let report = report::reordering(&layouts[0], &item);
+        assert_eq!(
+            report,
+            "reordering fields would save 4 bytes (12 -> 8):\n\
+             \x20   #[repr(C)]\n\
+             \x20   struct Tuple(\n\
+             \x20       u32,\n\
+             \x20       pub u8,\n\
+             \x20       u8,\n\
+             \x20   );\n"
+        );
+        let snippet: String = report.lines().skip(1).collect::<Vec<_>>().join("\n");
+        syn::parse_str::<syn::ItemStruct>(&snippet).unwrap();
+    }
+
+    #[test]
+    fn first_field_at_offset_zero() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                struct Default { a: u8, b: u32 }
+                #[repr(C)]
+                struct C { a: u8, b: u32 }
+                #[repr(packed)]
+                struct Packed { a: u8, b: u32 }
+                #[repr(packed(2))]
+                struct Packed2 { a: u8, b: u32 }
+                #[repr(C, align(16))]
+                struct Aligned { a: u64 }
+                #[repr(align(8))]
+                union U { a: u16, b: [u8; 3] }
+            }
+        });
+        let expected = [
+            (8, 4, 4),
+            (8, 4, 4),
+            (5, 1, 1),
+            (6, 2, 4),
+            (16, 16, 0),
+            (8, 8, 0),
+        ];
+        for (layout, &(size, align, second)) in layouts.iter().zip(&expected) {
+            assert_eq!(layout.total.size(), size);
+            assert_eq!(layout.total.align(), align);
+            match &layout.shape {
+                layout::Shape::Struct(x) => {
+                    assert_eq!(x.fields[0].offset, 0);
+                    if let Some(field) = x.fields.get(1) {
+                        assert_eq!(field.offset, second);
+                    }
+                }
+                layout::Shape::Union(x) => assert!(x.iter().all(|y| y.offset == 0)),
+                shape => panic!("unexpected shape {:?}", shape),
+            }
+        }
+
+        let misplaced = layout::FieldLayout {
+            name: "a".to_string(),
+            ty: parse_quote!(u8),
+            offset: 4,
+            layout: Layout::new::<u8>(),
+            internal_padding: 0,
+        };
+        assert_eq!(
+            check_first_offset(&parsed::TypePath(parse_quote!(S)), &[misplaced])
+                .unwrap_err()
+                .to_string(),
+            "internal pahole error while laying out `S`: first field `a` is at offset 4"
+        );
+    }
+
+    #[test]
+    fn repr_align_requires_a_literal() {
+        let err = try_analyze(parse_quote! {
+            #[repr(align(N))]
+            struct Aligned<const N: usize>(u8);
+        })
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`repr` arguments must be integer literals, not constants or const generic parameters"
+        );
+    }
+
+    #[test]
+    fn reanalyze_for_another_target() {
+        let mut data = Data::new();
+        data.set_target(layout::Target { pointer_width: 64 });
+        data.clear_processed();
+        data.add_layout(parsed::TypePath(parse_quote!(Handle)), Layout::new::<u32>());
+        data.add_item(
+            parsed::TypePath::new(),
+            parse_quote! {
+                mod m {
+                    struct Buf { data: &'static [u8], len: usize, handle: Handle }
+                }
+            },
+        )
+        .unwrap();
+        assert_eq!(data.resolve().unwrap()[0].total.size(), 32);
+
+        data.set_target(layout::Target { pointer_width: 32 });
+        data.clear_processed();
+        let layouts = data.resolve().unwrap();
+        assert_eq!(layouts[0].total.size(), 16);
+        assert_eq!(layouts[0].total.align(), 4);
+    }
+
+    #[test]
+    fn ffi_safety() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                #[repr(C)]
+                struct Header { len: u32, flags: u8, data: *const u8, next: Option<&'static Header> }
+                #[repr(transparent)]
+                struct Wrapper(Header);
+                struct Plain { a: u32 }
+                #[repr(C)]
+                struct HasPlain { a: [Plain; 2] }
+                #[repr(C)]
+                struct HasSlice { a: &'static [u8] }
+                enum E { A, B }
+            }
+        });
+        let reasons: Vec<Option<&str>> = layouts.iter().map(|x| x.ffi_unsafe.as_deref()).collect();
+        assert_eq!(
+            reasons,
+            [
+                None,
+                None,
+                Some("`m::Plain` has the default representation; add `#[repr(C)]`"),
+                Some(
+                    "field `a` is not FFI-safe: `m::Plain` has the default representation; add \
+                     `#[repr(C)]`"
+                ),
+                Some(
+                    "field `a` is not FFI-safe: pointers to unsized types are twice as large \
+                     as C pointers"
+                ),
+                Some("`m::E` is an enum without `#[repr(C)]` or an integer `repr`"),
+            ]
+        );
+        assert!(report::table(&layouts[0]).contains("\nFFI-safe: yes\n"));
+        assert!(report::table(&layouts[2]).contains("\nFFI-safe: no (`m::Plain` has"));
+    }
+
+    #[test]
+    fn simd_vectors() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                #[repr(simd)]
+                struct F32x4(f32, f32, f32, f32);
+                #[repr(simd)]
+                struct U8x3([u8; 3]);
+            }
+        });
+        assert_eq!(layouts[0].total, Layout::from_size_align(16, 16).unwrap());
+        assert_eq!(layouts[1].total, Layout::from_size_align(4, 4).unwrap());
+
+        let err = try_analyze(parse_quote! {
+            #[repr(simd)]
+            struct Mixed(f32, u32);
+        })
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`repr(simd)` requires all fields to have the same type"
+        );
+    }
+
+    #[test]
+    fn bytemap_marks_holes() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                struct S { a: u8, b: u32, c: u16, d: [u8; 68] }
+            }
+        });
+        assert_eq!(
+            report::bytemap(&layouts[0]),
+            format!(
+                "bytemap:\n       0  bbbb{}\n      64  {}cca.\n",
+                "d".repeat(60),
+                "d".repeat(8)
+            )
+        );
+    }
+
+    #[test]
+    fn reexports_are_followed() {
+        let layouts = analyze(parse_quote! {
+            mod api {
+                mod inner {
+                    pub struct Thing(u64, u8);
+                }
+                pub use self::inner::Thing;
+                pub use inner::{Thing as Renamed, self};
+
+                mod user {
+                    use super::Renamed;
+                    struct User {
+                        a: crate::api::Thing,
+                        b: Renamed,
+                        c: super::inner::Thing,
+                    }
+                }
+            }
+        });
+        assert_eq!(layouts[1].total.size(), 48);
+    }
+
+    #[test]
+    fn unknown_type_errors_point_at_the_type() {
+        let item = syn::parse_str("struct S {\n    a: u8,\n    b: Option<[(u16, Missing); 2]>,\n}")
+            .unwrap();
+        let err = try_analyze(item).unwrap_err();
+        assert_eq!(err.to_string(), "pahole could not find the type `Missing`");
+        let span = err.span();
+        assert_eq!((span.start().line, span.start().column), (3, 21));
+        assert_eq!((span.end().line, span.end().column), (3, 28));
+    }
+
+    #[test]
+    fn what_if_fields_change() {
+        let mut data = Data::new();
+        let item: Item = parse_quote! {
+            #[repr(C)]
+            struct A {
+                a: u8,
+                b: u32,
+                c: u16,
+            }
+        };
+        data.add_item(parsed::TypePath::new(), item.clone())
+            .unwrap();
+        let path = parsed::TypePath(parse_quote!(A));
+
+        let delta = data.what_if_field_removed(&path, "a").unwrap();
+        assert_eq!(delta.size_delta(), -4);
+        assert_eq!(
+            delta.moved,
+            vec![("b".to_string(), 4, 0), ("c".to_string(), 8, 4)]
+        );
+
+        let delta = data
+            .what_if_field_added(&path, "d", parse_quote!(u16))
+            .unwrap();
+        assert_eq!((delta.size_before, delta.size_after), (12, 12));
+        assert!(delta.moved.is_empty());
+        let delta = data
+            .what_if_field_added(&path, "d", parse_quote!(u64))
+            .unwrap();
+        assert_eq!(delta.size_delta(), 12);
+
+        assert_eq!(
+            data.what_if_field_added(&path, "b", parse_quote!(u8))
+                .unwrap_err()
+                .to_string(),
+            "`A` already has a field `b`"
+        );
+
+        // The original definition is left untouched.
+        assert_eq!(data.item_layout(&path).unwrap().total.size(), 12);
+        assert!(data.what_if_field_removed(&path, "e").is_err());
+
+        // Removing a tuple field renumbers the later ones, which are named by their old index.
+        data.add_item(
+            parsed::TypePath::new(),
+            parse_quote! {
+                #[repr(C)]
+                struct T(u8, u32, u16);
+            },
+        )
+        .unwrap();
+        let tuple = parsed::TypePath(parse_quote!(T));
+        let delta = data.what_if_field_removed(&tuple, "0").unwrap();
+        assert_eq!(
+            delta.moved,
+            vec![("1".to_string(), 4, 0), ("2".to_string(), 8, 4)]
+        );
+        let delta = data.what_if_field_removed(&tuple, "2").unwrap();
+        assert_eq!(delta.size_delta(), -4);
+        assert!(delta.moved.is_empty());
+
+        let delta = data.what_if_field_removed(&path, "a").unwrap();
+        assert_eq!(
+            report::what_if("removing `a` from `A`", &delta),
+            "removing `a` from `A` would change its size 12 -> 8 (-4)\n\
+             \x20 `b` would move from offset 4 to 0\n\
+             \x20 `c` would move from offset 8 to 4\n"
+        );
+        let options = vec![
+            parse_quote!(what_if_added("A.d: u16", "A.e: [u8; 3]")),
+            parse_quote!(what_if_removed("A.c")),
+        ];
+        assert!(run(options, item.clone()).unwrap().is_none());
+        let error = run(vec![parse_quote!(what_if_removed("A.e"))], item.clone()).unwrap_err();
+        assert_eq!(error.to_string(), "`A` has no field `e`");
+        let error = run(vec![parse_quote!(what_if_added("A.e"))], item).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            r#"expected a struct and a field to add, e.g. "S.flag: bool""#
+        );
+    }
+
+    #[test]
+    fn durations_use_the_std_layout() {
+        let mut data = Data::new();
+        let item: Item = parse_quote! {
+            mod m {
+                use std::time::Duration;
+
+                struct Timeout {
+                    flag: bool,
+                    after: Duration,
+                    retry: Option<core::time::Duration>,
+                }
+            }
+        };
+        data.add_item(parsed::TypePath::new(), item).unwrap();
+        let layouts = data.resolve().unwrap();
+        assert_eq!(layouts[0].total, Layout::from_size_align(40, 8).unwrap());
+        let note = report::std_layouts_note(&data.std_layouts_used);
+        assert!(note.contains("`core::time::Duration`, `std::time::Duration`"));
+    }
+
+    #[test]
+    fn trait_objects_are_fat_pointers() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                struct S {
+                    obj: Box<dyn std::fmt::Debug>,
+                    r: &'static (dyn Send + Sync),
+                    p: *const Tail,
+                }
+
+                struct Tail {
+                    len: u16,
+                    data: dyn std::any::Any,
+                }
+            }
+        });
+        let pointer = layout::Target::host().pointer().size();
+        match &layouts[0].shape {
+            layout::Shape::Struct(x) => {
+                let sizes: Vec<usize> = x.fields.iter().map(|y| y.layout.size()).collect();
+                assert_eq!(sizes, vec![2 * pointer; 3]);
+            }
+            _ => panic!("expected a struct"),
+        }
+        match &layouts[1].shape {
+            layout::Shape::Struct(x) => assert!(x.unsized_tail),
+            _ => panic!("expected a struct"),
+        }
+
+        let err = try_analyze(parse_quote! {
+            struct S {
+                obj: dyn std::fmt::Debug,
+                after: u8,
+            }
+        })
+        .unwrap_err();
+        assert!(err.to_string().contains("is unsized"));
+    }
+
+    #[test]
+    fn padding_after_the_tag_is_a_hole() {
+        let layouts = analyze(parse_quote! {
+            enum E {
+                A,
+                B(u64),
+            }
+        });
+        match &layouts[0].shape {
+            layout::Shape::Enum { variants, .. } => {
+                assert_eq!(
+                    variants[1].payload.holes,
+                    vec![layout::Hole { offset: 1, size: 7 }]
+                );
+            }
+            _ => panic!("expected an enum"),
+        }
+        let table = report::table(&layouts[0]);
+        assert!(table.contains(concat!(
+            "       0     1      1  <tag>\n",
+            "       1     7         <hole> (`0` is aligned to 8)\n",
+            "       8     8      8  0: u64\n",
+        )));
+    }
+
+    #[test]
+    fn only_reports_named_items() {
+        let mut data = Data::new();
+        let item: Item = parse_quote! {
+            mod m {
+                struct A(u8);
+                struct B(A, u16);
+                struct C(u32);
+            }
+        };
+        data.add_item(parsed::TypePath::new(), item).unwrap();
+        let names: Vec<syn::Path> = vec![parse_quote!(B), parse_quote!(D)];
+        let (layouts, missing) = data.resolve_only(&names).unwrap();
+        let paths: Vec<String> = layouts.iter().map(|x| x.path.to_string()).collect();
+        assert_eq!(paths, vec!["m::B"]);
+        assert_eq!(missing, vec![&names[1]]);
+        assert!(data
+            .processed_items
+            .contains_key(&parsed::TypePath(parse_quote!(m::A))));
+        assert!(!data
+            .processed_items
+            .contains_key(&parsed::TypePath(parse_quote!(m::C))));
+    }
+
+    #[test]
+    fn hot_prefix_fits_in_a_cache_line() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                struct Small(u64);
+                #[repr(C)]
+                struct Large {
+                    id: u32,
+                    name: [u8; 40],
+                    flags: u16,
+                    cold: [u64; 4],
+                }
+            }
+        });
+        assert_eq!(report::hot_prefix(&layouts[0]), "");
+        assert_eq!(
+            report::hot_prefix(&layouts[1]),
+            "first 3 fields (46 bytes) fit in cache line 0\n"
+        );
+    }
+
+    #[test]
+    fn zero_sized_fields_are_aligned() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                #[repr(C)]
+                struct Array {
+                    a: u8,
+                    z: [u64; 0],
+                    b: u8,
+                }
+
+                #[repr(C)]
+                struct Phantom {
+                    a: u8,
+                    z: std::marker::PhantomData<u64>,
+                    b: u8,
+                }
+            }
+        });
+        let offsets = |item: &layout::ItemLayout| match &item.shape {
+            layout::Shape::Struct(x) => x.fields.iter().map(|y| y.offset).collect::<Vec<_>>(),
+            _ => panic!("expected a struct"),
+        };
+        assert_eq!(layouts[0].total, Layout::from_size_align(16, 8).unwrap());
+        assert_eq!(offsets(&layouts[0]), vec![0, 8, 8]);
+        assert_eq!(layouts[1].total, Layout::from_size_align(2, 1).unwrap());
+        assert_eq!(offsets(&layouts[1]), vec![0, 1, 1]);
+        assert_eq!(layouts[1].ffi_unsafe, None);
+    }
+
+    #[test]
+    fn aos_soa_totals() {
+        let layouts = analyze(parse_quote! {
+            #[repr(C)]
+            struct Particle {
+                alive: bool,
+                x: f32,
+                kind: u16,
+            }
+        });
+        assert_eq!(
+            report::aos_soa(&layouts[0], 1024),
+            "array of 1024 structs: 12288 bytes (5120 bytes of padding)\n\
+             struct of 1024-element arrays: 7168 bytes\n\
+             splitting into arrays would save 5120 bytes\n"
+        );
+    }
+
+    #[test]
+    fn cfg_removes_disabled_fields() {
+        let item: Item = parse_quote! {
+            struct S {
+                a: u8,
+                #[cfg(feature = "pahole-cfg-test")]
+                b: u64,
+                #[cfg(not(feature = "pahole-cfg-test"))]
+                c: u16,
+                #[cfg(any(target_pointer_width = "16", target_pointer_width = "32"))]
+                d: u32,
+            }
+        };
+        let sized = |features: syn::NestedMeta, size: usize| {
+            run(vec![features, parse_quote!(size = #size)], item.clone())
+        };
+        assert!(sized(parse_quote!(features()), 4).is_ok());
+        assert!(sized(parse_quote!(features("pahole-cfg-test")), 16).is_ok());
+        // Cargo doesn't tell proc macros the enabled features, so they have to be listed.
+        let err = run(vec![], item).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("can't tell which features are enabled"));
+
+        let mut data = Data::new();
+        data.set_features(vec!["pahole-cfg-test".to_string()].into_iter().collect());
+        data.add_item(
+            parsed::TypePath::new(),
+            parse_quote! {
+                #[cfg(all(feature = "pahole-cfg-test", feature = "pahole-cfg-other"))]
+                struct Gone(u8);
+            },
+        )
+        .unwrap();
+        assert!(data.resolve().unwrap().is_empty());
+
+        let err = try_analyze(parse_quote! {
+            struct S {
+                #[cfg(test)]
+                a: u8,
+            }
+        })
+        .unwrap_err();
+        assert!(err.to_string().contains("can only evaluate"));
+    }
+
+    #[test]
+    fn scattered_flags_are_called_out() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                #[repr(C)]
+                struct One {
+                    a: u64,
+                    flag: bool,
+                    b: u64,
+                }
+
+                #[repr(C)]
+                struct Two {
+                    a: u64,
+                    x: bool,
+                    b: u64,
+                    y: u8,
+                    c: u64,
+                }
+
+                #[repr(C)]
+                struct Grouped {
+                    a: u64,
+                    x: bool,
+                    y: bool,
+                }
+            }
+        });
+        // Grouping `flag` at the end would only turn its hole into tail padding.
+        assert_eq!(
+            report::small_field_grouping(&layouts[0]),
+            "small fields `flag` are followed by 7 bytes of holes; grouping small fields after the \
+             larger ones would keep the size of 24, but leave room for more small fields\n"
+        );
+        assert_eq!(
+            report::small_field_grouping(&layouts[1]),
+            "small fields `x`, `y` are followed by holes; grouping small fields after the larger \
+             ones would remove 14 bytes of holes (size 40 -> 32)\n"
+        );
+        assert_eq!(report::small_field_grouping(&layouts[2]), "");
+
+        // The suggested layout ignores packing, so it would be larger here.
+        let layouts = analyze(parse_quote! {
+            #[repr(C, packed(2))]
+            struct Packed {
+                a: u8,
+                b: u32,
+            }
+        });
+        assert_eq!(report::small_field_grouping(&layouts[0]), "");
+    }
+
+    #[test]
+    fn qualified_primitives_resolve() {
+        let layouts = analyze(parse_quote! {
+            #[repr(C)]
+            struct S {
+                a: std::primitive::u8,
+                b: core::primitive::u32,
+                c: ::std::primitive::bool,
+                d: core::primitive::usize,
+                e: Option<std::primitive::char>,
+            }
+        });
+        let sizes: Vec<usize> = match &layouts[0].shape {
+            layout::Shape::Struct(x) => x.fields.iter().map(|y| y.layout.size()).collect(),
+            _ => panic!("expected a struct"),
+        };
+        let pointer = layout::Target::host().pointer().size();
+        assert_eq!(sizes, vec![1, 4, 1, pointer, 4]);
+        assert!(try_analyze(parse_quote! {
+            struct S(std::primitive::Missing);
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn layout_docs_are_prepended() {
+        let mut item: Item = parse_quote! {
+            mod m {
+                /// Existing docs.
+                struct A {
+                    a: u8,
+                    b: u64,
+                }
+
+                struct B(u8);
+
+                struct Generic<T>(T);
+            }
+        };
+        let mut data = Data::new();
+        data.add_item(parsed::TypePath::new(), item.clone())
+            .unwrap();
+        let layouts = data
+            .resolve_only(&[parse_quote!(A), parse_quote!(B)])
+            .unwrap()
+            .0;
+        let by_path = layouts.iter().map(|x| (&x.path, x)).collect();
+        add_layout_docs(&mut item, &parsed::TypePath::new(), &by_path);
+        let items = match item {
+            Item::Mod(x) => x.content.unwrap().1,
+            _ => unreachable!(),
+        };
+        let docs = |item: &Item| match item {
+            Item::Struct(x) => x
+                .attrs
+                .iter()
+                .map(|y| match y.parse_meta().unwrap() {
+                    syn::Meta::NameValue(syn::MetaNameValue {
+                        lit: Lit::Str(lit), ..
+                    }) => lit.value(),
+                    _ => panic!("expected a doc attribute"),
+                })
+                .collect::<Vec<_>>(),
+            _ => panic!("expected a struct"),
+        };
+        assert_eq!(
+            docs(&items[0]),
+            vec![" Layout: size=16, align=8, 0 holes", "", " Existing docs."]
+        );
+        assert_eq!(docs(&items[1]), vec![" Layout: size=1, align=1, 0 holes"]);
+        assert!(docs(&items[2]).is_empty());
+    }
+
+    #[test]
+    fn runs_of_small_integers_may_be_bitfields() {
+        let layouts = analyze(parse_quote! {
+            #[repr(C)]
+            struct Flags {
+                a: u8,
+                b: u8,
+                c: u8,
+                len: u32,
+                d: u16,
+                e: u8,
+            }
+        });
+        assert_eq!(
+            report::bitfield_note(&layouts[0]),
+            "note: fields `a`, `b`, `c` (u8) could be C bitfields; a C struct declaring them as \
+             bitfields would have a different layout\n"
+        );
+    }
+
+    #[test]
+    fn option_niches_nest_until_exhausted() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                #[repr(C)]
+                struct Pointers {
+                    boxed: Option<Box<u64>>,
+                    reference: Option<&'static u64>,
+                    non_null: Option<std::ptr::NonNull<u64>>,
+                }
+
+                #[repr(C)]
+                struct Nested {
+                    exhausted: Option<Option<&'static u64>>,
+                    flag: Option<Option<Option<bool>>>,
+                    retagged: Option<Option<Option<Box<u64>>>>,
+                }
+            }
+        });
+        let sizes = |item: &layout::ItemLayout| match &item.shape {
+            layout::Shape::Struct(x) => {
+                x.fields.iter().map(|y| y.layout.size()).collect::<Vec<_>>()
+            }
+            _ => panic!("expected a struct"),
+        };
+        let pointer = layout::Target::host().pointer().size();
+        assert_eq!(sizes(&layouts[0]), vec![pointer; 3]);
+        assert_eq!(sizes(&layouts[1]), vec![2 * pointer, 1, 2 * pointer]);
+    }
+
+    #[test]
+    fn named_fields_without_names_are_errors() {
+        let mut item: syn::ItemStruct = parse_quote! {
+            struct S {
+                a: u8,
+            }
+        };
+        if let Fields::Named(x) = &mut item.fields {
+            x.named[0].ident = None;
+        }
+        let err = try_analyze(Item::Struct(item)).unwrap_err();
+        assert_eq!(err.to_string(), "expected a named field");
+
+        let mut item: syn::ItemUnion = parse_quote! {
+            union U {
+                a: u8,
+            }
+        };
+        item.fields.named[0].ident = None;
+        assert!(try_analyze(Item::Union(item)).is_err());
+    }
+
+    #[test]
+    fn internal_padding_of_nested_tuples_and_arrays() {
+        let layouts = analyze(parse_quote! {
+            struct S {
+                env: ([u8; 3], u64),
+                pairs: [(u8, u16); 4],
+                nested: ((u8, u32), [u64; 2]),
+                plain: u64,
+            }
+        });
+        let padding: Vec<usize> = match &layouts[0].shape {
+            layout::Shape::Struct(x) => x.fields.iter().map(|y| y.internal_padding).collect(),
+            _ => panic!("expected a struct"),
+        };
+        assert_eq!(padding, vec![5, 4, 3, 0]);
+        assert!(report::table(&layouts[0])
+            .contains("  field `env` contains 5 bytes of internal padding\n"));
+    }
+
+    #[test]
+    fn dependency_graph_edges() {
+        let mut data = Data::new();
+        let item: Item = parse_quote! {
+            mod m {
+                struct Inner(u8);
+                struct Outer {
+                    a: Inner,
+                    b: Option<Inner>,
+                    c: Box<Outer>,
+                    d: [Other; 2],
+                }
+                struct Other;
+            }
+        };
+        data.add_item(parsed::TypePath::new(), item.clone())
+            .unwrap();
+        let layouts = data.resolve().unwrap();
+        let path = |x: syn::TypePath| parsed::TypePath(x);
+        assert_eq!(
+            data.dependency_graph()[&path(parse_quote!(m::Outer))],
+            vec![path(parse_quote!(m::Inner)), path(parse_quote!(m::Other))]
+        );
+        assert!(!data
+            .dependency_graph()
+            .contains_key(&path(parse_quote!(m::Inner))));
+        assert_eq!(
+            report::dependency_graph(&layouts, data.dependency_graph()),
+            "embedded types:\n\
+             \x20 `m::Outer` -> `m::Inner`, `m::Other`\n"
+        );
+        assert!(run(vec![parse_quote!(dependency_graph)], item)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn layout_compatible_groups() {
+        let mut data = Data::new();
+        let item: Item = parse_quote! {
+            mod m {
+                #[repr(C)]
+                struct A {
+                    x: u32,
+                    y: f32,
+                }
+                #[repr(C)]
+                struct B(i32, u32);
+                struct C {
+                    x: u32,
+                    y: f32,
+                }
+                struct D(i32, u32);
+                struct E {
+                    a: u32,
+                    b: f32,
+                }
+            }
+        };
+        data.add_item(parsed::TypePath::new(), item).unwrap();
+        let layouts = data.resolve().unwrap();
+        let report = report::layout_compatible(&layouts, |x| data.has_stable_layout(&x.path));
+        let groups: Vec<&str> = report.lines().skip(1).take(2).collect();
+        assert_eq!(groups, vec!["  `m::A`, `m::B`", "  `m::C`, `m::E`"]);
+    }
+
+    #[test]
+    fn generic_defaults_are_substituted() {
+        let mut data = Data::new();
+        let item: Item = parse_quote! {
+            mod m {
+                #[repr(C)]
+                struct S<'a, T = u32, U = [T; 2]> {
+                    x: T,
+                    y: Option<&'a U>,
+                    z: U,
+                }
+                enum E<T = u16> {
+                    A(T),
+                    B,
+                }
+            }
+        };
+        data.add_item(parsed::TypePath::new(), item).unwrap();
+        let layouts = data.resolve().unwrap();
+        let pointer = layout::Target::host().pointer().size();
+        match &layouts[0].shape {
+            layout::Shape::Struct(x) => {
+                let sizes: Vec<usize> = x.fields.iter().map(|y| y.layout.size()).collect();
+                assert_eq!(sizes, vec![4, pointer, 8]);
+            }
+            _ => panic!("expected a struct"),
+        }
+        assert_eq!(layouts[1].total.size(), 4);
+        assert_eq!(
+            data.default_instantiations[&layouts[0].path],
+            "S<u32, [u32; 2]>"
+        );
+    }
+
+    #[test]
+    fn sort_orders() {
+        let mut layouts = analyze(parse_quote! {
+            mod m {
+                struct B(u8, u64);
+                struct C(u8, u16);
+                struct A([u64; 4]);
+                enum D {
+                    X(u8, u32),
+                    Y,
+                }
+            }
+        });
+        let names = |layouts: &[layout::ItemLayout]| {
+            layouts
+                .iter()
+                .map(|x| x.path.to_string())
+                .collect::<Vec<_>>()
+        };
+        report::sort(&mut layouts, options::SortOrder::Name);
+        assert_eq!(names(&layouts), vec!["m::A", "m::B", "m::C", "m::D"]);
+        report::sort(&mut layouts, options::SortOrder::Size);
+        assert_eq!(names(&layouts), vec!["m::A", "m::B", "m::D", "m::C"]);
+        report::sort(&mut layouts, options::SortOrder::Padding);
+        assert_eq!(names(&layouts), vec!["m::B", "m::D", "m::C", "m::A"]);
+        let padding: Vec<usize> = layouts.iter().map(|x| x.padding()).collect();
+        assert_eq!(padding, vec![7, 2, 1, 0]);
+    }
+
+    #[test]
+    fn extern_blocks() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                extern "C" {
+                    type Opaque;
+                    static HANDLE: *mut Opaque;
+                    fn open() -> *mut Opaque;
+                }
+
+                #[repr(C)]
+                struct Wrapper {
+                    raw: *const Opaque,
+                    len: u32,
+                }
+            }
+        });
+        let pointer = layout::Target::host().pointer();
+        assert_eq!(layouts[0].total, pointer);
+        assert!(matches!(layouts[0].shape, layout::Shape::Value(_)));
+        assert_eq!(layouts[1].ffi_unsafe, None);
+
+        let err = try_analyze(parse_quote! {
+            mod m {
+                extern "C" {
+                    type Opaque;
+                }
+                struct ByValue(Opaque);
+            }
+        })
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("can only be used behind a pointer"));
+    }
+
+    #[test]
+    fn hypothetical_repr() {
+        #[allow(dead_code)]
+        struct Default {
+            a: u8,
+            b: u32,
+            c: u8,
+        }
+        #[allow(dead_code)]
+        #[repr(C)]
+        struct C {
+            a: u8,
+            b: u32,
+            c: u8,
+        }
+
+        let mut data = Data::new();
+        let item: Item = parse_quote! {
+            struct S {
+                a: u8,
+                b: u32,
+                c: u8,
+            }
+        };
+        data.add_item(parsed::TypePath::new(), item).unwrap();
+        let actual = data.resolve().unwrap().remove(0);
+        let path = actual.path.clone();
+        let repr = |tokens: proc_macro2::TokenStream| {
+            parse_repr(&[parse_quote!(#[repr(#tokens)])]).unwrap()
+        };
+
+        // rustc moves `c` next to `a`, but `repr(C)` keeps the declaration order.
+        let c = data
+            .layout_with_repr(&path, repr(quote::quote!(C)))
+            .unwrap();
+        assert_eq!(actual.total, Layout::new::<Default>());
+        assert_eq!(c.total, Layout::new::<C>());
+        assert!(c.total.size() > actual.total.size());
+        assert!(actual.ffi_unsafe.is_some());
+        assert_eq!(c.ffi_unsafe, None);
+
+        let packed = data
+            .layout_with_repr(&path, repr(quote::quote!(C, packed)))
+            .unwrap();
+        assert_eq!(packed.total, Layout::from_size_align(6, 1).unwrap());
+        // The real type is untouched.
+        assert_eq!(data.item_layout(&path).unwrap().total, actual.total);
+    }
+
+    #[test]
+    fn tail_padding_is_repeated_in_arrays() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                struct Padded(u64, u8);
+                #[repr(C)]
+                struct Holey(u8, u64);
+            }
+        });
+        assert_eq!(
+            report::array_waste(&layouts[0]),
+            "each array element wastes 7 tail-padding bytes; a [m::Padded; 1000] wastes 7000\n"
+        );
+        assert_eq!(report::array_waste(&layouts[1]), "");
+    }
+
+    #[test]
+    fn aliases_to_generic_instantiations() {
+        let mut data = Data::new();
+        let item: Item = parse_quote! {
+            mod m {
+                pub struct Inner(u64);
+
+                pub mod wrappers {
+                    pub struct Wrapper<T, U = u8> {
+                        value: T,
+                        extra: U,
+                    }
+                }
+
+                type Handle = wrappers::Wrapper<u32>;
+                type Nested = wrappers::Wrapper<Inner, wrappers::Wrapper<u16>>;
+                struct Uses<'a>(&'a Handle, Option<wrappers::Wrapper<bool, bool>>);
+            }
+        };
+        data.add_item(parsed::TypePath::new(), item).unwrap();
+        let layouts = data.resolve().unwrap();
+        let names: Vec<String> = layouts.iter().map(|x| x.path.to_string()).collect();
+        assert_eq!(names, vec!["m::Inner", "m::Handle", "m::Nested", "m::Uses"]);
+        assert_eq!(layouts[1].total, Layout::from_size_align(8, 4).unwrap());
+        assert_eq!(layouts[2].total, Layout::from_size_align(16, 8).unwrap());
+
+        let instance = data.alias_instance(&layouts[1]).unwrap().unwrap();
+        assert_eq!(instance.path.to_string(), "m::wrappers::Wrapper<u32>");
+        let instance = data.alias_instance(&layouts[2]).unwrap().unwrap();
+        assert_eq!(
+            instance.path.to_string(),
+            "m::wrappers::Wrapper<crate::m::Inner, crate::m::wrappers::Wrapper<u16>>"
+        );
+        match &instance.shape {
+            layout::Shape::Struct(x) => {
+                let sizes: Vec<usize> = x.fields.iter().map(|y| y.layout.size()).collect();
+                assert_eq!(sizes, vec![8, 4]);
+            }
+            _ => panic!("expected a struct"),
+        }
+
+        let err = try_analyze(parse_quote! {
+            mod m {
+                struct Wrapper<T>(T);
+                type Bare = Wrapper;
+            }
+        })
+        .unwrap_err();
+        assert_eq!(err.to_string(), "missing a generic argument for `T`");
+    }
+
+    #[test]
+    fn references_into_packed_structs() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                #[repr(packed)]
+                struct Packed {
+                    a: u8,
+                    b: u32,
+                    c: [u8; 3],
+                }
+                #[repr(packed(2))]
+                struct Packed2(u16, u64);
+            }
+        });
+        assert_eq!(
+            report::packed_references(&layouts[0], 1),
+            "warning: field `b` needs alignment 4 but packing only guarantees 1, so references to \
+             it are undefined behavior; copy it out instead\n"
+        );
+        let warnings = report::packed_references(&layouts[1], 2);
+        assert_eq!(warnings.lines().count(), 1);
+        assert!(warnings.starts_with("warning: field `1` needs alignment 8"));
+    }
+
+    #[test]
+    fn results_use_niches_like_user_enums() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                #[repr(C)]
+                struct Results {
+                    tagged: Result<u32, u32>,
+                    niche: Result<NonZeroU32, ()>,
+                    boxed: Result<Box<u64>, ()>,
+                    nested: Option<Result<NonZeroU32, ()>>,
+                }
+            }
+        });
+        let sizes = match &layouts[0].shape {
+            layout::Shape::Struct(x) => {
+                x.fields.iter().map(|y| y.layout.size()).collect::<Vec<_>>()
+            }
+            _ => panic!("expected a struct"),
+        };
+        let pointer = layout::Target::host().pointer().size();
+        assert_eq!(sizes, vec![8, 4, pointer, 8]);
+    }
+
+    #[test]
+    fn incremental_analysis_recomputes_dependents() {
+        let dir = std::env::temp_dir().join(format!("pahole-incremental-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let a = dir.join("a.rs");
+        let b = dir.join("b.rs");
+        std::fs::write(&a, "pub struct A { x: u32 } pub struct Lone(u8);").unwrap();
+        std::fs::write(&b, "pub struct B { a: crate::a::A } pub struct C(u16);").unwrap();
+
+        let names = |layouts: Vec<layout::ItemLayout>| {
+            layouts
+                .iter()
+                .map(|x| x.path.to_string())
+                .collect::<Vec<_>>()
+        };
+        let mut analyzer = incremental::Analyzer::new();
+        let layouts = analyzer
+            .analyze_incremental(&[a.clone(), b.clone()])
+            .unwrap();
+        assert_eq!(names(layouts), vec!["a::A", "a::Lone", "b::B", "b::C"]);
+
+        std::fs::write(&a, "pub struct A { x: u64 } pub struct Lone(u8);").unwrap();
+        let layouts = analyzer
+            .analyze_incremental(std::slice::from_ref(&a))
+            .unwrap();
+        let b_size = layouts
+            .iter()
+            .find(|x| x.path.to_string() == "b::B")
+            .map(|x| x.total.size());
+        assert_eq!(b_size, Some(8));
+        assert_eq!(names(layouts), vec!["b::B", "a::A", "a::Lone"]);
+
+        // A file that doesn't parse leaves the whole batch, and so `B`, as it was.
+        std::fs::write(&a, "pub struct A { x: u8 } pub struct Lone(u8);").unwrap();
+        std::fs::write(&b, "pub struct B { a: crate::a::A").unwrap();
+        assert!(analyzer
+            .analyze_incremental(&[a.clone(), b.clone()])
+            .is_err());
+        std::fs::write(&b, "pub struct B { a: crate::a::A } pub struct C(u16);").unwrap();
+        let layouts = analyzer.analyze_incremental(&[b]).unwrap();
+        assert_eq!(layouts[0].total.size(), 8);
+        let layouts = analyzer.analyze_incremental(&[a]).unwrap();
+        assert_eq!(names(layouts), vec!["b::B", "a::A", "a::Lone"]);
+
+        let other = dir.join("other");
+        std::fs::create_dir_all(&other).unwrap();
+        let same_stem = other.join("a.rs");
+        std::fs::write(&same_stem, "pub struct D;").unwrap();
+        let error = analyzer
+            .analyze_incremental(std::slice::from_ref(&same_stem))
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "`{}` and `{}` would both be the module `a`",
+                dir.join("a.rs").display(),
+                same_stem.display()
+            )
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn discriminant_types_fit_the_discriminants() {
+        let variants: Vec<String> = (0..300).map(|i| format!("V{}", i)).collect();
+        let source = format!(
+            "mod m {{ enum Small {{ A, B, C }} enum Large {{ {} }} enum Negative {{ A = -1, B }} \
+             enum Sparse {{ A, B = 70000 }} }}",
+            variants.join(", ")
+        );
+        let layouts = analyze(syn::parse_str(&source).unwrap());
+        let tags: Vec<_> = layouts
+            .iter()
+            .map(|x| match &x.shape {
+                layout::Shape::Enum {
+                    discriminant: layout::Discriminant::Tag(tag),
+                    ..
+                } => tag.name(),
+                _ => panic!("expected a tagged enum"),
+            })
+            .collect();
+        assert_eq!(tags, vec!["u8", "u16", "i8", "u32"]);
+        assert!(report::table(&layouts[0]).contains("discriminant type: u8 (1 byte)\n"));
+        assert!(report::table(&layouts[1]).contains("discriminant type: u16 (2 bytes)\n"));
+    }
+
+    #[test]
+    fn packed_structs_nest_in_c_structs() {
+        macro_rules! both {
+            ($layouts:ident; $($item:item)*) => {
+                $(#[allow(dead_code)] $item)*
+                let $layouts = analyze(parse_quote!(mod m { $($item)* }));
+            };
+        }
+        both! {
+            layouts;
+            #[repr(C, packed)]
+            struct Inner {
+                a: u8,
+                b: u32,
+            }
+
+            #[repr(C)]
+            struct Outer {
+                x: u8,
+                inner: Inner,
+                y: u16,
+                z: u64,
+            }
+        }
+        let offsets = match &layouts[1].shape {
+            layout::Shape::Struct(x) => x.fields.iter().map(|y| y.offset).collect::<Vec<_>>(),
+            _ => panic!("expected a struct"),
+        };
+        use std::mem::{align_of, offset_of, size_of};
+        assert_eq!(
+            offsets,
+            vec![
+                offset_of!(Outer, x),
+                offset_of!(Outer, inner),
+                offset_of!(Outer, y),
+                offset_of!(Outer, z),
+            ]
+        );
+        assert_eq!(layouts[0].total.size(), size_of::<Inner>());
+        assert_eq!(layouts[0].total.align(), align_of::<Inner>());
+        assert_eq!(layouts[1].total.size(), size_of::<Outer>());
+    }
+
+    #[test]
+    fn enums_with_c_and_integer_reprs() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                #[repr(C, u8)]
+                enum Both {
+                    A,
+                    B(u64),
+                    C(u8),
+                }
+
+                #[repr(u8)]
+                enum Primitive {
+                    A,
+                    B(u8, u32),
+                }
+
+                #[repr(C)]
+                enum Fieldless {
+                    A,
+                    B,
+                }
+
+                #[repr(i16)]
+                enum Signed {
+                    A,
+                    B,
+                }
+            }
+        });
+        let sizes: Vec<_> = layouts.iter().map(|x| x.total.size()).collect();
+        assert_eq!(sizes, vec![16, 8, 4, 2]);
+        let table = report::table(&layouts[0]);
+        assert!(table.contains("FFI-safe: yes\n"));
+        assert!(table.contains("discriminant type: u8 (1 byte)\n"));
+        // The union of the fields starts after the tag, so `C`'s field is at 8 rather than 1.
+        match &layouts[0].shape {
+            layout::Shape::Enum { variants, .. } => {
+                assert_eq!(variants[2].payload.fields[0].offset, 8);
+                assert_eq!(
+                    variants[2].payload.holes,
+                    vec![layout::Hole { offset: 1, size: 7 }]
+                );
+            }
+            _ => panic!("expected an enum"),
+        }
+        match &layouts[1].shape {
+            layout::Shape::Enum { variants, .. } => {
+                let offsets: Vec<_> = variants[1]
+                    .payload
+                    .fields
+                    .iter()
+                    .map(|x| x.offset)
+                    .collect();
+                assert_eq!(offsets, vec![1, 4]);
+            }
+            _ => panic!("expected an enum"),
+        }
+        assert!(report::table(&layouts[3]).contains("discriminant type: i16 (2 bytes)\n"));
+
+        let error = try_analyze(parse_quote! {
+            #[repr(u8)]
+            struct S(u8);
+        })
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "integer `repr`s can only be used on enums"
+        );
+    }
+
+    #[test]
+    fn reports_are_headed_by_their_label() {
+        let layouts = analyze(parse_quote! {
+            struct S(u8);
+        });
+        let args: AttributeArgs = vec![parse_quote!(label = "hot-path")];
+        let options = options::Options::parse(args).unwrap();
+        assert_eq!(
+            report::header(&layouts[0], options.label.as_deref()),
+            "== hot-path ==\n"
+        );
+        assert_eq!(report::header(&layouts[0], None), "== S ==\n");
+    }
+
+    #[test]
+    fn arrays_of_structs_repeat_their_padding() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                struct Inner {
+                    a: u8,
+                    b: u64,
+                }
+
+                struct Outer {
+                    inners: [Inner; 100],
+                }
+            }
+        });
+        assert_eq!(layouts[1].total.size(), layouts[0].total.size() * 100);
+        assert!(report::table(&layouts[1]).contains(
+            "  field `inners` contains 700 bytes of internal padding (7 in each of 100 elements)\n"
+        ));
+    }
+
+    #[test]
+    fn same_layout_ignores_names_and_declaration_order() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                #[repr(C)]
+                struct Original {
+                    id: u32,
+                    flags: u16,
+                    kind: u16,
+                }
+
+                #[repr(C)]
+                struct Renamed {
+                    key: u32,
+                    kind: u16,
+                    flags: u16,
+                }
+
+                #[repr(C)]
+                struct Reordered {
+                    flags: u16,
+                    id: u32,
+                    kind: u16,
+                }
+            }
+        });
+        assert!(layout::same_layout(&layouts[0], &layouts[1]));
+        assert!(!layout::same_layout(&layouts[0], &layouts[2]));
+    }
+
+    #[test]
+    fn flag_bits_only_list_integer_newtypes() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                struct Flags(u32);
+                struct Pair(u32, u32);
+            }
+        });
+        let bits = report::flag_bits(&layouts[0], 32);
+        assert!(bits.starts_with("bits of `m::Flags`:\n  bit  0 (byte 0)\n"));
+        assert!(bits.ends_with("  bit 31 (byte 3)\n"));
+        assert_eq!(report::flag_bits(&layouts[1], 32), "");
+    }
+
+    #[test]
+    fn generic_aliases_are_instantiated() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                type Pair<T> = (T, T);
+
+                struct Holder<T> {
+                    pair: Pair<T>,
+                    flag: bool,
+                }
+
+                struct Uses {
+                    direct: Pair<u32>,
+                    nested: Holder<u16>,
+                }
+            }
+        });
+        assert_eq!(layouts.len(), 1);
+        let sizes = match &layouts[0].shape {
+            layout::Shape::Struct(x) => {
+                x.fields.iter().map(|y| y.layout.size()).collect::<Vec<_>>()
+            }
+            _ => panic!("expected a struct"),
+        };
+        assert_eq!(sizes, vec![8, 6]);
+    }
+
+    #[test]
+    fn size_and_align_options_check_the_item() {
+        let item: Item = parse_quote! {
+            struct Header {
+                kind: u32,
+                length: u32,
+                offset: u64,
+            }
+        };
+        assert!(run(
+            vec![parse_quote!(size = 16), parse_quote!(align = 8)],
+            item.clone()
+        )
+        .is_ok());
+        let error = run(vec![parse_quote!(size = 24), parse_quote!(align = 4)], item).unwrap_err();
+        let messages: Vec<_> = error.into_iter().map(|x| x.to_string()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "expected `Header` to be 24 bytes, but it is 16 bytes",
+                "expected `Header` to be aligned to 4 bytes, but it is aligned to 8 bytes",
+            ]
+        );
+    }
+
+    #[test]
+    fn small_fields_are_suggested_for_holes() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                #[repr(C)]
+                struct S {
+                    a: u8,
+                    b: u32,
+                    flag: u8,
+                }
+
+                // Moving either of `c` and `flag` alone just moves the padding to the end.
+                #[repr(C)]
+                struct T {
+                    a: u32,
+                    b: u64,
+                    c: u16,
+                    flag: u8,
+                }
+            }
+        });
+        assert_eq!(
+            report::hole_fillings(&layouts[0]),
+            "move `flag` (1 byte) into the 3-byte hole after `a` (size 12 -> 8)\n"
+        );
+        assert_eq!(report::hole_fillings(&layouts[1]), "");
+    }
+
+    #[test]
+    fn manually_drop_is_transparent_in_unions() {
+        let layouts = analyze(parse_quote! {
+            union U {
+                text: std::mem::ManuallyDrop<String>,
+                number: u32,
+            }
+        });
+        let pointer = layout::Target::host().pointer();
+        assert_eq!(layouts[0].total.size(), 3 * pointer.size());
+        assert_eq!(layouts[0].total.align(), pointer.align());
+        let table = report::table(&layouts[0]);
+        assert!(table.contains(&format!(
+            "       0    {:>2}  {:>5}  text: std::mem::ManuallyDrop<String>\n",
+            3 * pointer.size(),
+            pointer.align()
+        )));
+        assert!(table.contains("  `text` is `ManuallyDrop`, so it has the layout of `String`\n"));
+    }
+
+    #[test]
+    fn unknown_types_can_be_assumed() {
+        let item: Item = parse_quote! {
+            struct S {
+                a: other_crate::Handle,
+                b: u8,
+                c: other_crate::Handle,
+            }
+        };
+        let layout_with = |unknown| {
+            let mut data = Data::new();
+            data.set_unknown_types(unknown);
+            data.add_item(parsed::TypePath::new(), item.clone())
+                .unwrap();
+            let layouts = data.resolve().unwrap();
+            assert_eq!(data.assumed_types, vec!["other_crate::Handle"]);
+            layouts[0].total
+        };
+        let pointer = layout::Target::host().pointer();
+        assert_eq!(
+            layout_with(options::UnknownTypes::Pointer).size(),
+            3 * pointer.size()
+        );
+        assert_eq!(layout_with(options::UnknownTypes::Skip).size(), 1);
+        let sized = layout_with(options::UnknownTypes::Size(6));
+        assert_eq!((sized.size(), sized.align()), (14, 2));
+        assert!(try_analyze(item).is_err());
+    }
+
+    #[test]
+    fn optional_fn_pointers_are_pointer_sized() {
+        let layouts = analyze(parse_quote! {
+            #[repr(C)]
+            struct Callbacks {
+                on_open: Option<extern "C" fn(*mut u8) -> i32>,
+                on_close: Option<extern "C" fn()>,
+                on_error: Option<fn(u32)>,
+            }
+        });
+        let pointer = layout::Target::host().pointer();
+        assert_eq!(layouts[0].total.size(), 3 * pointer.size());
+        let table = report::table(&layouts[0]);
+        assert!(table.contains("  field `on_close` stores `None` as a null pointer\n"));
+        assert!(table.contains(
+            "FFI-safe: no (field `on_error` is not FFI-safe: `fn` pointers use the Rust calling \
+             convention; use `extern \"C\" fn`)\n"
+        ));
+    }
+
+    #[test]
+    fn primitives_must_be_registered_without_builtins() {
+        let item: Item = parse_quote! {
+            struct S {
+                a: u8,
+                b: u16,
+            }
+        };
+        let mut data = Data::without_builtins();
+        data.add_item(parsed::TypePath::new(), item).unwrap();
+        assert_eq!(
+            data.resolve().unwrap_err().to_string(),
+            "pahole could not find the type `u8`"
+        );
+        data.add_layout(
+            parsed::TypePath(parse_quote!(u8)),
+            Layout::from_size_align(1, 1).unwrap(),
+        );
+        data.add_layout(
+            parsed::TypePath(parse_quote!(u16)),
+            Layout::from_size_align(2, 1).unwrap(),
+        );
+        assert_eq!(data.resolve().unwrap()[0].total.size(), 3);
+    }
+
+    #[test]
+    fn tags_are_packed_into_payload_padding() {
+        let layouts = analyze(parse_quote! {
+            enum E {
+                A(u32, u8),
+                B(u64),
+            }
+        });
+        assert_eq!(layouts[0].total.size(), 16);
+        let offsets = match &layouts[0].shape {
+            layout::Shape::Enum { variants, .. } => variants[0]
+                .payload
+                .fields
+                .iter()
+                .map(|x| (x.name.clone(), x.offset))
+                .collect::<Vec<_>>(),
+            _ => panic!("expected an enum"),
+        };
+        assert_eq!(offsets, vec![("1".to_string(), 1), ("0".to_string(), 4)]);
+        assert!(report::table(&layouts[0])
+            .contains("discriminant packed into payload padding of variant `A`\n"));
+    }
+
+    #[test]
+    fn expected_holes_are_checked_and_removed() {
+        let item: Item = parse_quote! {
+            #[repr(C)]
+            struct Message {
+                kind: u8,
+                #[pahole_hole(3)]
+                length: u32,
+            }
+        };
+        let emitted = match run(Vec::new(), item).unwrap().map(syn::parse2) {
+            Some(Ok(Item::Struct(x))) => x,
+            _ => panic!("expected the struct to be emitted"),
+        };
+        assert!(emitted.fields.iter().all(|x| x.attrs.is_empty()));
+
+        let error = run(
+            Vec::new(),
+            parse_quote! {
+                #[repr(C)]
+                struct Message {
+                    kind: u16,
+                    #[pahole_hole(3)]
+                    length: u32,
+                }
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "expected a 3-byte hole before `length`, but the hole is 2 bytes"
+        );
+    }
+
+    #[test]
+    fn zero_initializable_structs() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                struct Zeroable {
+                    a: u32,
+                    b: bool,
+                    p: *const u8,
+                    o: Option<&'static u8>,
+                }
+                struct Borrowed {
+                    a: u32,
+                    r: &'static u8,
+                }
+                enum E {
+                    A(&'static u8),
+                    B,
+                }
+            }
+        });
+        assert!(report::table(&layouts[0]).contains("zero-initializable: yes\n"));
+        assert!(report::table(&layouts[1])
+            .contains("zero-initializable: no (field `r`: references can't be null)\n"));
+        assert_eq!(layouts[2].zero_invalid, None);
+    }
+
+    #[test]
+    fn optional_arrays() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                type Bytes = Option<[u8; 4]>;
+                type Refs = Option<[&'static u8; 2]>;
+                type Empty = Option<[&'static u8; 0]>;
+            }
+        });
+        assert_eq!(layouts[0].total.size(), 5);
+        // Like rustc, the niche of the first element is used.
+        assert_eq!(
+            layouts[1].total.size(),
+            std::mem::size_of::<Option<[&u8; 2]>>()
+        );
+        assert_eq!(
+            layouts[2].total.size(),
+            std::mem::size_of::<Option<[&u8; 0]>>()
+        );
+    }
+
+    #[test]
+    fn type_paths_are_displayed_as_written() {
+        let path = parsed::TypePath::from_ident(parse_quote!(a))
+            .concat(parsed::TypePath(parse_quote!(b::C<u8, Vec<u16>>)));
+        assert_eq!(path.to_string(), "a::b::C<u8, Vec<u16>>");
+        let reparsed = parsed::TypePath(syn::parse_str(&path.to_string()).unwrap());
+        assert_eq!(reparsed, path);
+        assert_eq!(
+            parsed::TypePath(parse_quote!(::std::num::NonZeroU8)).to_string(),
+            "::std::num::NonZeroU8"
+        );
+    }
+
+    #[test]
+    fn flexible_array_members() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                #[repr(C)]
+                struct Buf {
+                    len: u32,
+                    data: [u8; 0],
+                }
+                #[repr(C)]
+                struct Words {
+                    len: u8,
+                    data: [u64; 0],
+                }
+                struct NotC {
+                    len: u32,
+                    data: [u8; 0],
+                }
+            }
+        });
+        assert_eq!(layouts[0].total, Layout::from_size_align(4, 4).unwrap());
+        assert!(report::table(&layouts[0]).contains(
+            "`data` is a flexible array member starting at offset 4; the size excludes it\n"
+        ));
+        assert_eq!(layouts[1].total, Layout::from_size_align(8, 8).unwrap());
+        assert!(report::table(&layouts[1]).contains("starting at offset 8;"));
+        assert!(!report::table(&layouts[2]).contains("flexible array member"));
+    }
+
+    #[test]
+    fn layout_macros_are_named_after_the_path() {
+        let tokens = run(
+            vec![parse_quote!(emit_table_macro)],
+            parse_quote! {
+                mod m {
+                    struct Pair(u8, u32);
+                    struct Generic<T>(T);
+                }
+            },
+        )
+        .unwrap()
+        .unwrap();
+        let file: syn::File = syn::parse2(tokens).unwrap();
+        let macros: Vec<syn::ItemMacro> = file
+            .items
+            .into_iter()
+            .filter_map(|x| match x {
+                Item::Macro(x) => Some(x),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(macros.len(), 1);
+        assert_eq!(macros[0].ident.as_ref().unwrap(), "layout_of_m_Pair");
+        let body = report::tokens_to_string(&macros[0].mac.tokens);
+        assert!(body.contains("(offset 0) => { 4usize }"), "{}", body);
+    }
+
+    #[test]
+    fn field_tables_show_alignments() {
+        let layouts = analyze(parse_quote! {
+            #[repr(C)]
+            struct Mixed {
+                a: u8,
+                b: u32,
+                c: u16,
+            }
+        });
+        assert!(report::table(&layouts[0]).contains(concat!(
+            "  offset  size  align  field\n",
+            "       0     1      1  a: u8\n",
+            "       1     3         <hole> (`b` is aligned to 4)\n",
+            "       4     4      4  b: u32\n",
+            "       8     2      2  c: u16\n",
+            "      10     2         <padding>\n",
+        )));
+    }
+
+    #[test]
+    fn discriminant_values_are_reported() {
+        let layouts = analyze(parse_quote! {
+            enum F {
+                A = 1,
+                B = 4,
+                C = 9,
+            }
+        });
+        assert_eq!(layouts[0].total, Layout::from_size_align(1, 1).unwrap());
+        let table = report::table(&layouts[0]);
+        assert!(table.contains("discriminant type: u8 (1 byte)\n"));
+        assert!(table.contains("variant A (discriminant 1):\n"));
+        assert!(table.contains("variant B (discriminant 4):\n"));
+        assert!(table.contains("variant C (discriminant 9):\n"));
+        assert!(table.contains("the gaps between discriminants don't affect the size\n"));
+    }
+
+    #[test]
+    fn boxed_fields_are_compared_with_inlining() {
+        let mut data = Data::new();
+        data.set_target(layout::Target { pointer_width: 64 });
+        data.add_item(
+            parsed::TypePath::new(),
+            parse_quote! {
+                mod m {
+                    struct Inner {
+                        a: u64,
+                        b: [u32; 4],
+                    }
+                    struct Outer {
+                        inner: Box<Inner>,
+                        id: u32,
+                    }
+                    struct List {
+                        next: Option<Box<List>>,
+                        tail: Box<List>,
+                    }
+                }
+            },
+        )
+        .unwrap();
+        let layouts = data.resolve().unwrap();
+        let boxed = data.boxed_fields(&layouts[1]).unwrap();
+        assert_eq!(
+            report::boxed_fields(&layouts[1], &boxed),
+            "field `inner: Box<Inner>`: 8 bytes (inline would be 24 bytes, aligned to 8, making `m::Outer` 32 bytes instead of 16)\n"
+        );
+        assert!(data.boxed_fields(&layouts[2]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn tuple_variants_have_their_own_tables() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                enum E {
+                    A,
+                    B(u32),
+                    C { n: i128 },
+                    D(u8, u64),
+                    T((u8, u64)),
+                }
+                enum Only {
+                    D(u8, u64),
+                }
+            }
+        });
+        let table = report::table(&layouts[0]);
+        assert!(table.contains(concat!(
+            "variant B (discriminant 1):\n",
+            "  offset  size  align  field\n",
+            "       0     1      1  <tag>\n",
+            "       1     3         <hole> (`0` is aligned to 4)\n",
+            "       4     4      4  0: u32\n",
+        )));
+        assert!(table.contains("      16    16     16  n: i128\n"));
+        // The `u8` shares the tag's alignment unit, leaving a smaller hole.
+        assert!(table.contains(concat!(
+            "       1     1      1  0: u8\n",
+            "       2     6         <hole> (`1` is aligned to 8)\n",
+            "       8     8      8  1: u64\n",
+        )));
+        assert!(table.contains("  field `0` contains 7 bytes of internal padding\n"));
+        assert!(report::table(&layouts[1]).contains(concat!(
+            "       0     1      1  0: u8\n",
+            "       1     7         <hole> (`1` is aligned to 8)\n",
+        )));
+    }
+
+    #[test]
+    fn aliases_are_expanded_unless_kept() {
+        let mut data = Data::new();
+        data.add_item(
+            parsed::TypePath::new(),
+            parse_quote! {
+                mod m {
+                    type Word = u64;
+                    mod ids {
+                        pub type Id = super::Word;
+                    }
+                    struct S {
+                        w: Word,
+                        ids: Option<[ids::Id; 2]>,
+                    }
+                }
+            },
+        )
+        .unwrap();
+        let mut layouts = data.resolve().unwrap();
+        let table = report::table(&layouts[2]);
+        assert!(table.contains("  w: Word\n"));
+        assert!(table.contains("  ids: Option<[ids::Id; 2]>\n"));
+
+        data.expand_aliases(&mut layouts[2]);
+        let table = report::table(&layouts[2]);
+        assert!(table.contains("  w: u64\n"));
+        assert!(table.contains("  ids: Option<[u64; 2]>\n"));
+
+        let keep = |value: bool| {
+            options::Options::parse(vec![parse_quote!(expand_aliases = #value)])
+                .unwrap()
+                .keep_aliases
+        };
+        assert!(keep(false));
+        assert!(!keep(true));
+    }
+
+    #[test]
+    fn phantom_data_ignores_its_argument() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                struct Markers {
+                    a: u32,
+                    object: std::marker::PhantomData<dyn std::fmt::Debug>,
+                    slice: std::marker::PhantomData<[u8]>,
+                    f: std::marker::PhantomData<fn(&str) -> Missing>,
+                }
+                #[repr(C)]
+                struct Ffi {
+                    a: u32,
+                    slice: std::marker::PhantomData<[u8]>,
+                }
+            }
+        });
+        assert_eq!(layouts[0].total, Layout::from_size_align(4, 4).unwrap());
+        match &layouts[0].shape {
+            layout::Shape::Struct(x) => {
+                assert!(x.fields[..3]
+                    .iter()
+                    .all(|y| y.layout == Layout::new::<()>()));
+                assert!(!x.unsized_tail);
+            }
+            _ => panic!("expected a struct"),
+        }
+        assert_eq!(layouts[0].zero_invalid, None);
+        assert_eq!(layouts[1].ffi_unsafe, None);
+    }
+
+    #[test]
+    fn compact_format() {
+        let layouts = analyze(parse_quote! {
+            #[repr(C)]
+            struct A {
+                b: u64,
+                c: u8,
+            }
+        });
+        assert_eq!(
+            report::compact(&layouts[0]),
+            concat!(
+                "A: size=16 align=8 pad=7 holes=0\n",
+                "A.b @0 size=8 align=8\n",
+                "A.c @8 size=1 align=1\n",
+            )
+        );
+        let layouts = analyze(parse_quote! {
+            mod m {
+                enum E {
+                    A,
+                    B(u16),
+                }
+            }
+        });
+        assert_eq!(
+            report::compact(&layouts[0]),
+            concat!(
+                "m::E: size=4 align=2 pad=1 holes=1\n",
+                "m::E::B.0 @2 size=2 align=2\n",
+            )
+        );
+    }
+
+    #[test]
+    fn drop_glue_is_noted() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                struct Named {
+                    id: u32,
+                    name: String,
+                }
+                struct Plain {
+                    id: u32,
+                    name: &'static str,
+                    kept: std::mem::ManuallyDrop<Vec<u8>>,
+                }
+                enum Message {
+                    Quit,
+                    Rename(Option<Named>),
+                }
+            }
+        });
+        assert!(report::table(&layouts[0])
+            .contains("has drop glue (field `name`: `String` has drop glue)\n"));
+        assert_eq!(layouts[1].drop_glue, None);
+        assert!(!report::table(&layouts[1]).contains("drop glue"));
+        assert_eq!(
+            layouts[2].drop_glue.as_deref(),
+            Some("variant `Rename`, field `0`: field `name`: `String` has drop glue")
+        );
+    }
+
+    #[test]
+    fn generic_arrays_use_registered_type_numbers() {
+        let mut data = Data::new();
+        data.add_type_number(parsed::TypePath(parse_quote!(typenum::U16)), 16);
+        data.add_item(
+            parsed::TypePath::new(),
+            parse_quote! {
+                mod m {
+                    struct Key {
+                        bytes: generic_array::GenericArray<u8, typenum::U16>,
+                    }
+                    struct Wide(generic_array::GenericArray<u32, typenum::U16>);
+                }
+            },
+        )
+        .unwrap();
+        let layouts = data.resolve().unwrap();
+        assert_eq!(layouts[0].total, Layout::from_size_align(16, 1).unwrap());
+        assert_eq!(layouts[1].total, Layout::from_size_align(64, 4).unwrap());
+    }
+
+    #[test]
+    fn instance_totals_multiply_size_and_padding() {
+        let layouts = analyze(parse_quote! {
+            #[repr(C)]
+            struct Padded {
+                a: u8,
+                b: u64,
+            }
+        });
+        assert_eq!(
+            report::instance_totals(&layouts[0], 1000),
+            "1000 instances of `Padded` take 16000 bytes (15.6 KiB), wasting 7000 bytes (6.8 KiB) on padding\n"
+        );
+        assert_eq!(
+            report::instance_totals(&layouts[0], 10),
+            "10 instances of `Padded` take 160 bytes, wasting 70 bytes on padding\n"
+        );
+    }
+
+    #[test]
+    fn bounds_and_where_clauses_are_ignored() {
+        let mut data = Data::new();
+        data.add_item(
+            parsed::TypePath::new(),
+            parse_quote! {
+                mod m {
+                    struct S<T: Clone, U: ?Sized = [u8]>
+                    where
+                        T: Default + 'static,
+                        for<'a> &'a U: IntoIterator,
+                    {
+                        x: T,
+                        tail: std::marker::PhantomData<U>,
+                    }
+                    struct Uses {
+                        s: S<u32>,
+                    }
+                }
+            },
+        )
+        .unwrap();
+        let layouts = data.resolve().unwrap();
+        assert_eq!(layouts[0].total, Layout::from_size_align(4, 4).unwrap());
+        let instance = data
+            .lookup_instance(&layouts[0].path, &parse_quote!(S<u32>))
+            .unwrap();
+        let instance = data.item_layout(&instance).unwrap();
+        assert!(report::table(&instance).contains("       0     4      4  x: u32\n"));
+    }
+
+    #[test]
+    fn internals_are_only_dumped_on_request() {
+        let mut data = Data::new();
+        data.add_item(
+            parsed::TypePath::new(),
+            parse_quote!(
+                struct S(u8);
+            ),
+        )
+        .unwrap();
+        let options = |args| options::Options::parse(args).unwrap();
+        assert_eq!(debug_parse(&options(Vec::new()), &data), "");
+        let dump = debug_parse(&options(vec![parse_quote!(debug_parse)]), &data);
+        assert!(dump.starts_with("Data {\n"));
+        assert!(dump.contains("unprocessed_items"));
+    }
+
+    #[test]
+    fn const_generics_are_bound_by_position() {
+        let mut data = Data::new();
+        data.add_item(
+            parsed::TypePath::new(),
+            parse_quote! {
+                mod m {
+                    struct Buf<T, const N: usize> {
+                        len: u32,
+                        data: [T; N],
+                    }
+                    struct Small {
+                        buf: Buf<u8, 4>,
+                    }
+                }
+            },
+        )
+        .unwrap();
+        let layouts = data.resolve().unwrap();
+        assert_eq!(layouts[0].total, Layout::from_size_align(8, 4).unwrap());
+
+        let options =
+            options::Options::parse(vec![parse_quote!(monomorphize("m::Buf<u16, 16>"))]).unwrap();
+        let buf = data.monomorphize(&options.monomorphize[0]).unwrap();
+        assert_eq!(buf.path.to_string(), "m::Buf<u16, 16>");
+        assert_eq!(buf.total, Layout::from_size_align(36, 4).unwrap());
+        assert!(report::table(&buf).contains("       0    32      2  data: [u16; 16]\n"));
+
+        let error = data.monomorphize(&parse_quote!(m::Buf<8, u8>)).unwrap_err();
+        assert_eq!(error.to_string(), "expected a type argument for `T`");
+    }
+
+    #[test]
+    fn c_layouts_are_checked_field_by_field() {
+        let layouts = analyze(parse_quote! {
+            #[repr(C)]
+            struct Header {
+                kind: u8,
+                len: u32,
+                flags: u16,
+            }
+        });
+        let file: LitStr = parse_quote!("header.toml");
+        let matching = sizes::parse(concat!(
+            "Header = { size = 12, align = 4 }\n",
+            "\"Header.kind\" = { offset = 0, size = 1 }\n",
+            "\"Header.len\" = { offset = 4, size = 4 }\n",
+            "\"Header.flags\" = { offset = 8, size = 2 }\n",
+        ))
+        .unwrap();
+        check_c_layout(&file, &matching, &layouts).unwrap();
+
+        let mismatching = sizes::parse(concat!(
+            "Header = { size = 8, align = 4 }\n",
+            "\"Header.kind\" = { offset = 0, size = 1 }\n",
+            "\"Header.len\" = { offset = 2, size = 4 }\n",
+            "\"Header.crc\" = { offset = 6, size = 2 }\n",
+        ))
+        .unwrap();
+        assert_eq!(
+            check_c_layout(&file, &mismatching, &layouts)
+                .unwrap_err()
+                .to_string(),
+            concat!(
+                "`Header` doesn't match its C layout:\n",
+                "  size: 12 in Rust, but 8 in C\n",
+                "  field `len`: offset 4, size 4 in Rust, but offset 2, size 4 in C\n",
+                "  field `crc`: only in C\n",
+                "  field `flags`: only in Rust",
+            )
+        );
+    }
+
+    #[test]
+    fn arrays_of_enums_repeat_their_unused_bytes() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                enum E {
+                    Big(u64),
+                    Small(u8),
+                }
+                struct Table {
+                    entries: [E; 100],
+                }
+            }
+        });
+        assert_eq!(layouts[0].total.size(), 16);
+        assert_eq!(
+            report::array_waste(&layouts[0]),
+            "each array element wastes 7 bytes unused by any variant; a [m::E; 1000] wastes 7000\n"
+        );
+        assert_eq!(layouts[1].total.size(), 1600);
+        assert!(report::table(&layouts[1]).contains(
+            "  field `entries` contains 700 bytes of internal padding (7 in each of 100 elements)\n"
+        ));
+    }
+
+    #[test]
+    fn c_tuple_structs_are_laid_out_positionally() {
+        let layouts = analyze(parse_quote! {
+            #[repr(C)]
+            struct Point(u8, f64, u16);
+        });
+        assert_eq!(layouts[0].total, Layout::from_size_align(24, 8).unwrap());
+        assert_eq!(layouts[0].ffi_unsafe, None);
+        assert!(report::table(&layouts[0]).contains(concat!(
+            "       0     1      1  0: u8\n",
+            "       1     7         <hole> (`1` is aligned to 8)\n",
+            "       8     8      8  1: f64\n",
+            "      16     2      2  2: u16\n",
+            "      18     6         <padding>\n",
+        )));
+    }
+
+    #[test]
+    fn layout_mods_name_tuple_fields() {
+        let tokens = run(
+            vec![parse_quote!(emit_mod)],
+            parse_quote! {
+                #[repr(C)]
+                struct Pair(u8, u32);
+            },
+        )
+        .unwrap()
+        .unwrap();
+        let file: syn::File = syn::parse2(tokens).unwrap();
+        let module = match &file.items[1] {
+            Item::Mod(x) => x,
+            _ => panic!("expected a module"),
+        };
+        assert_eq!(module.ident, "Pair_layout");
+        let body = report::tokens_to_string(&module.content.as_ref().unwrap().1[2]);
+        assert_eq!(
+            body,
+            "pub mod offsets { pub const _0: usize = 0usize; pub const _1: usize = 4usize; }"
+        );
+    }
+
+    #[test]
+    fn c_bools_are_ffi_safe_bytes() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                #[repr(C)]
+                struct Flags {
+                    enabled: bool,
+                    count: u16,
+                    visible: bool,
+                }
+                struct RustFlags {
+                    enabled: bool,
+                }
+            }
+        });
+        assert_eq!(layouts[0].total, Layout::from_size_align(6, 2).unwrap());
+        match &layouts[0].shape {
+            layout::Shape::Struct(x) => {
+                assert_eq!(x.fields[0].layout, Layout::from_size_align(1, 1).unwrap());
+                assert_eq!(x.fields[2].offset, 4);
+            }
+            _ => panic!("expected a struct"),
+        }
+        assert_eq!(layouts[0].ffi_unsafe, None);
+        assert_eq!(
+            report::bool_note(&layouts[0]),
+            "note: `bool` fields `enabled`, `visible` match C's `_Bool`, one byte holding 0 or 1\n"
+        );
+        assert_eq!(layouts[1].total, Layout::from_size_align(1, 1).unwrap());
+        assert!(layouts[1].ffi_unsafe.is_some());
+    }
+
+    #[test]
+    fn registered_niches_collapse_options() {
+        let mut data = Data::new();
+        data.add_layout(parsed::TypePath(parse_quote!(Handle)), Layout::new::<u32>());
+        data.register_niche(parsed::TypePath(parse_quote!(Handle)), 1);
+        data.register_niche(parsed::TypePath(parse_quote!(m::Fd)), 1);
+        data.add_item(
+            parsed::TypePath::new(),
+            parse_quote! {
+                mod m {
+                    struct Fd(i32);
+                    struct S { a: Option<Handle>, b: Option<Fd>, c: Option<Option<Fd>> }
+                }
+            },
+        )
+        .unwrap();
+        data.clear_processed();
+        let layouts = data.resolve().unwrap();
+        assert_eq!(layouts[1].total.size(), 16);
+        assert_eq!(
+            sizes::parse("Fd = { niche = 1 }\nHandle = { size = 4, align = 4, niche = 1 }")
+                .unwrap()
+                .iter()
+                .map(|entry| sizes::entry_registration(entry).unwrap().1)
+                .collect::<Vec<_>>(),
+            vec![
+                sizes::Registered::Niche(1),
+                sizes::Registered::Layout(Layout::new::<u32>(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn nested_items_are_reported_by_qualified_path() {
+        let layouts = analyze(parse_quote! {
+            mod a {
+                mod b {
+                    mod c {
+                        struct Inner(u8);
+                    }
+                }
+                mod d {
+                    struct Inner(u64);
+                }
+            }
+        });
+        assert_eq!(
+            layouts
+                .iter()
+                .map(|x| x.path.to_string())
+                .collect::<Vec<_>>(),
+            ["a::b::c::Inner", "a::d::Inner"]
+        );
+        assert!(report::header(&layouts[0], None).contains("== a::b::c::Inner =="));
+        assert!(report::table(&layouts[1]).starts_with("a::d::Inner: size 8, align 8\n"));
+        assert!(report::compact(&layouts[1]).starts_with("a::d::Inner: size=8"));
+    }
+
+    #[test]
+    fn packed_baselines_sum_the_field_sizes() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                struct Holes { a: u8, b: u64, c: u16 }
+                enum E { A }
+            }
+        });
+        assert_eq!(
+            report::packed_baseline(&layouts[0]),
+            "packed baseline: 11 bytes without any padding, 5 fewer than the actual size\n"
+        );
+        assert_eq!(report::packed_baseline(&layouts[1]), "");
+    }
+
+    #[test]
+    fn pointer_dependent_layouts_are_symbolic() {
+        let mut data = Data::new();
+        data.add_item(
+            parsed::TypePath::new(),
+            parse_quote! {
+                mod m {
+                    struct S { len: usize, flag: u8 }
+                    struct Header { id: u64, name: &'static str }
+                    struct Fixed(u32);
+                }
+            },
+        )
+        .unwrap();
+        let by_width = data.resolve_for_each_pointer_width().unwrap();
+        let item = |i: usize| by_width.iter().map(|x| &x[i]).collect::<Vec<_>>();
+        assert_eq!(
+            report::symbolic(&item(0)),
+            "`m::S` depends on the pointer width: size 2 * ptr, align ptr\n\
+             \x20 field `len`: offset 0, size ptr\n\
+             \x20 field `flag`: offset ptr, size 1\n"
+        );
+        assert_eq!(
+            report::symbolic(&item(1)),
+            "`m::Header` depends on the pointer width: size 16 with 16-bit pointers, 16 with \
+             32-bit pointers, 24 with 64-bit pointers, align 8\n\
+             \x20 field `id`: offset 0 with 16-bit pointers, 8 with 32-bit pointers, 16 with 64-bit \
+             pointers, size 8\n\
+             \x20 field `name`: offset 8 with 16-bit pointers, 0 with 32-bit pointers, 0 with \
+             64-bit pointers, size 2 * ptr\n"
+        );
+        assert_eq!(report::symbolic(&item(2)), "");
+        assert_eq!(
+            data.resolve().unwrap()[0].total,
+            Layout::new::<(usize, u8)>()
+        );
+    }
+
+    #[test]
+    fn layouts_can_be_looked_up_by_path() {
+        let layouts = analyze_by_path(parse_quote! {
+            mod a {
+                mod b {
+                    struct C { x: u64, y: u8 }
+                }
+                struct C(u8);
+            }
+        });
+        assert_eq!(layouts["a::b::C"].total.size(), 16);
+        assert_eq!(layouts["a::C"].total.size(), 1);
+        assert_eq!(layouts.len(), 2);
+    }
+
+    #[test]
+    fn aligned_c_unions_see_through_manually_drop() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                #[repr(C, align(16))]
+                union U { a: ManuallyDrop<String>, b: u128, c: [u8; 33] }
+            }
+        });
+        let fields = match &layouts[0].shape {
+            layout::Shape::Union(x) => x,
+            _ => panic!("expected a union"),
+        };
+        assert!(fields.iter().all(|x| x.offset == 0));
+        assert_eq!(fields[0].layout, Layout::new::<String>());
+        assert_eq!(layouts[0].total, Layout::from_size_align(48, 16).unwrap());
+    }
+
+    #[test]
+    fn compact_target_diffs_mark_differing_fields() {
+        let mut data = Data::new();
+        data.add_item(
+            parsed::TypePath::new(),
+            parse_quote! {
+                mod m {
+                    struct S { flag: u8, len: usize }
+                }
+            },
+        )
+        .unwrap();
+        let by_width = data.resolve_for_each_pointer_width().unwrap();
+        let items: Vec<_> = by_width.iter().map(|x| &x[0]).collect();
+        assert_eq!(
+            report::compact_target_diff(&items),
+            "m::S: size=4/8/16 align=2/4/8 differs\n\
+             m::S.len @0/0/0 size=2/4/8 align=2/4/8 differs\n\
+             m::S.flag @2/4/8 size=1/1/1 align=1/1/1 differs\n"
+        );
+    }
+
+    #[test]
+    fn union_fields_are_shown_under_the_containing_field() {
+        let mut data = Data::new();
+        data.add_item(
+            parsed::TypePath::new(),
+            parse_quote! {
+                mod m {
+                    #[repr(C)]
+                    union Value { i: u32, f: f64, bytes: [u8; 12] }
+                    #[repr(C)]
+                    struct Tagged { tag: u8, value: Value }
+                }
+            },
+        )
+        .unwrap();
+        let layouts = data.resolve().unwrap();
+        assert_eq!(layouts[0].total, Layout::from_size_align(16, 8).unwrap());
+        let outer = match &layouts[1].shape {
+            layout::Shape::Struct(x) => x,
+            _ => panic!("expected a struct"),
+        };
+        assert_eq!(outer.fields[1].offset, 8);
+        assert_eq!(layouts[1].total.size(), 24);
+        let unions = data.union_fields(&layouts[1]).unwrap();
+        assert_eq!(
+            report::union_fields(&layouts[1], &unions),
+            "field `value: Value` is a union whose fields overlap:\n\
+             \x20      8     4      4    i: u32\n\
+             \x20      8     8      8    f: f64\n\
+             \x20      8    12      1    bytes: [u8; 12]\n"
+        );
+        assert!(data.union_fields(&layouts[0]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn field_percentages_add_up_to_the_size() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                struct S { a: u8, b: u64, c: u16 }
+                struct Empty;
+            }
+        });
+        let report = report::field_percentages(&layouts[0]);
+        assert_eq!(
+            report,
+            "shares of the 16 bytes of `m::S`, counting holes toward the field after them:\n\
+             \x20  50.0%  b\n\
+             \x20  12.5%  c\n\
+             \x20   6.2%  a\n\
+             \x20  31.2%  <padding>\n"
+        );
+        let total: f64 = report
+            .lines()
+            .skip(1)
+            .map(|x| x.trim().split('%').next().unwrap().parse::<f64>().unwrap())
+            .sum();
+        assert!((total - 100.0).abs() < 0.1);
+        assert_eq!(report::field_percentages(&layouts[1]), "");
+    }
+
+    #[test]
+    fn arrays_of_unknown_types_use_the_assumed_layout() {
+        let mut data = Data::new();
+        data.set_target(layout::Target { pointer_width: 64 });
+        data.clear_processed();
+        data.set_unknown_types(options::UnknownTypes::Pointer);
+        data.add_item(
+            parsed::TypePath::new(),
+            parse_quote! {
+                struct S { a: [Missing; 4], b: [[Missing; 2]; 3] }
+            },
+        )
+        .unwrap();
+        let layouts = data.resolve().unwrap();
+        let layout = match &layouts[0].shape {
+            layout::Shape::Struct(x) => x,
+            _ => panic!("expected a struct"),
+        };
+        assert_eq!(layout.fields[0].layout.size(), 32);
+        assert_eq!(layout.fields[1].layout.size(), 48);
+        assert_eq!(data.assumed_types, vec!["Missing"]);
+    }
+
+    #[test]
+    fn natural_alignments_are_reported_when_overridden() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                #[repr(align(16))]
+                struct Raised(u64);
+                #[repr(packed)]
+                struct Lowered(u8, u64);
+                #[repr(C)]
+                struct Natural(u8, u64);
+            }
+        });
+        assert!(report::table(&layouts[0])
+            .contains("\nnatural alignment 8, effective alignment 16 (repr(align))\n"));
+        assert!(report::table(&layouts[1])
+            .contains("\nnatural alignment 8, effective alignment 1 (repr(packed))\n"));
+        assert!(!report::table(&layouts[2]).contains("natural alignment"));
+    }
+
+    #[test]
+    fn whole_files_are_analyzed() {
+        let file: syn::File = parse_quote! {
+            use std::fmt;
+
+            struct Point { x: f64, y: f64 }
+
+            impl fmt::Display for Point {
+                fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    write!(f, "({}, {})", self.x, self.y)
+                }
+            }
+
+            mod shapes {
+                fn area() -> f64 { 0.0 }
+                pub enum Shape { Dot(super::Point), Line(super::Point, super::Point) }
+            }
+
+            fn main() {}
+        };
+        let layouts = analyze_syn_file(&file, None, None).unwrap();
+        assert_eq!(
+            layouts
+                .iter()
+                .map(|x| x.path.to_string())
+                .collect::<Vec<_>>(),
+            ["Point", "shapes::Shape"]
+        );
+        assert_eq!(layouts[0].total, Layout::new::<(f64, f64)>());
+        assert_eq!(layouts[1].total.size(), 40);
+    }
+
+    #[test]
+    fn maybe_uninit_has_the_layout_of_its_argument() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                struct Buffer(core::mem::MaybeUninit<[u8; 4096]>);
+                struct Bytes([MaybeUninit<u8>; 4096]);
+                #[repr(C)]
+                struct Slot { value: MaybeUninit<String>, used: bool }
+                enum E { A(MaybeUninit<&'static u8>), B }
+            }
+        });
+        assert_eq!(layouts[0].total, Layout::from_size_align(4096, 1).unwrap());
+        assert_eq!(layouts[1].total, Layout::from_size_align(4096, 1).unwrap());
+        assert_eq!(layouts[2].zero_invalid, None);
+        assert_eq!(layouts[2].drop_glue, None);
+        assert!(layouts[2].ffi_unsafe.as_ref().unwrap().contains("`String`"));
+        // Unlike `ManuallyDrop`, `MaybeUninit` has no niche, since it can hold any bytes.
+        assert_eq!(
+            layouts[3].total,
+            Layout::new::<Option<std::mem::MaybeUninit<&u8>>>()
+        );
+    }
+
+    #[test]
+    fn ascending_alignments_only_warn_when_reversal_saves_bytes() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                #[repr(C)]
+                struct Worst { a: u8, b: u16, c: u32, d: u64 }
+                #[repr(C)]
+                struct Wide { a: u8, b: u64 }
+                #[repr(C)]
+                struct Mixed { a: u8, b: u64, c: u16 }
+                #[repr(C)]
+                struct NoHoles { a: [u8; 4], b: u32 }
+            }
+        });
+        // Reversing `Worst` and `Wide` only turns their holes into tail padding.
+        for layout in &layouts {
+            assert_eq!(report::ascending_alignment(layout), "");
+        }
+        match &layouts[1].shape {
+            layout::Shape::Struct(x) => assert_eq!(x.hole_bytes(), 7),
+            _ => panic!("expected a struct"),
+        }
+    }
+
+    #[test]
+    fn cows_of_strs_and_slices_are_laid_out_like_enums() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                struct Config<'a> {
+                    name: Cow<'a, str>,
+                    data: std::borrow::Cow<'static, [u32]>,
+                }
+            }
+        });
+        let layout = match &layouts[0].shape {
+            layout::Shape::Struct(x) => x,
+            _ => panic!("expected a struct"),
+        };
+        let cow = Layout::new::<std::borrow::Cow<str>>();
+        assert_eq!(layout.fields[0].layout, cow);
+        assert_eq!(layout.fields[1].layout, cow);
+        let layouts = analyze(parse_quote! {
+            struct S { name: Option<Cow<'static, str>> }
+        });
+        assert_eq!(
+            layouts[0].total.size(),
+            std::mem::size_of::<Option<std::borrow::Cow<'static, str>>>()
+        );
+        assert_eq!(
+            layouts[0].drop_glue.as_deref(),
+            Some("field `name`: `String` has drop glue")
+        );
+        let err = try_analyze(parse_quote! {
+            struct S { path: Cow<'static, Path> }
+        })
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the layout of `Cow<'static, Path>` depends on the owned type of `Path`, which pahole \
+             only knows for `str` and slices"
+        );
+    }
+
+    #[test]
+    fn ffi_safety_can_be_required() {
+        let check = |item: Item| {
+            let layouts = analyze(item.clone());
+            let by_path = layouts.iter().map(|x| (&x.path, x)).collect();
+            let mut errors = None;
+            check_ffi_safe(&item, &parsed::TypePath::new(), &by_path, &mut errors);
+            errors.map(|x| x.into_iter().map(|y| y.to_string()).collect::<Vec<_>>())
+        };
+        assert!(check(parse_quote! {
+            mod m {
+                #[repr(C)]
+                struct Header { len: u32, data: *const u8 }
+                #[repr(transparent)]
+                struct Wrapper(Header);
+            }
+        })
+        .is_none());
+        assert_eq!(
+            check(parse_quote! {
+                mod m {
+                    struct Plain { a: u32 }
+                    #[repr(C)]
+                    struct Tuple(u8, (u8, u8));
+                    #[repr(u8)]
+                    enum E { A { s: &'static str } }
+                }
+            })
+            .unwrap(),
+            [
+                "`m::Plain` is not FFI-safe: `m::Plain` has the default representation; add \
+                 `#[repr(C)]`",
+                "`m::Tuple` is not FFI-safe: field `1` is not FFI-safe: tuples have no C \
+                 equivalent",
+                "`m::E` is not FFI-safe: field `s` of variant `A` is not FFI-safe: pointers to \
+                 unsized types are twice as large as C pointers",
+            ]
+        );
+    }
+
+    #[test]
+    fn multidimensional_arrays() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                struct Grid { cells: [[u8; 3]; 4] }
+                struct Matrix([[u64; 2]; 2]);
+                struct Cube { voxels: [[[(u16, u8); 2]; 3]; 4] }
+            }
+        });
+        let field = |i: usize| match &layouts[i].shape {
+            layout::Shape::Struct(x) => x.fields[0].clone(),
+            _ => panic!("expected a struct"),
+        };
+        assert_eq!(field(0).layout, Layout::new::<[[u8; 3]; 4]>());
+        assert_eq!(field(1).layout, Layout::new::<[[u64; 2]; 2]>());
+        assert_eq!(field(2).layout, Layout::new::<[[[(u16, u8); 2]; 3]; 4]>());
+        assert_eq!(field(2).internal_padding, 24);
+    }
+
+    #[test]
+    fn ad_hoc_types_are_reported() {
+        let report = type_report(&parse_quote!((u8, u64))).unwrap();
+        assert!(report.starts_with("== (u8, u64) ==\nType: size 16, align 8\n"));
+        assert!(report.ends_with("  = (u8, u64)\n"));
+        assert_eq!(
+            type_report(&parse_quote!([Missing; 2]))
+                .unwrap_err()
+                .to_string(),
+            try_analyze(parse_quote!(
+                type Type = [Missing; 2];
+            ))
+            .unwrap_err()
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn default_repr_fields_are_reordered_like_rustc() {
+        #[allow(dead_code)]
+        struct S {
+            a: u8,
+            b: u64,
+            c: u8,
+        }
+        #[allow(dead_code)]
+        struct Mixed {
+            flag: bool,
+            name: &'static str,
+            id: u16,
+            marker: std::marker::PhantomData<u8>,
+            bytes: [u8; 3],
+            next: Option<Box<u32>>,
+        }
+
+        let layouts = analyze(parse_quote! {
+            mod m {
+                struct S {
+                    a: u8,
+                    b: u64,
+                    c: u8,
+                }
+                struct Mixed {
+                    flag: bool,
+                    name: &'static str,
+                    id: u16,
+                    marker: std::marker::PhantomData<u8>,
+                    bytes: [u8; 3],
+                    next: Option<Box<u32>>,
+                }
+                struct Tuple((u8, u64, u8), (u16, u8, u32));
+            }
+        });
+        assert_eq!(layouts[0].total, Layout::new::<S>());
+        assert_eq!(layouts[1].total, Layout::new::<Mixed>());
+        match &layouts[2].shape {
+            layout::Shape::Struct(x) => {
+                assert_eq!(x.fields[0].layout, Layout::new::<(u8, u64, u8)>());
+                assert_eq!(x.fields[1].layout, Layout::new::<(u16, u8, u32)>());
+            }
+            _ => panic!("expected a struct"),
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn layouts_round_trip_through_golden_files() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                struct S<'a> { a: u8, name: &'a str, b: [u16; 3] }
+                #[repr(C, align(16))]
+                struct Aligned(u32);
+                enum E { A(u32), B, C(bool) }
+                enum Niche { Some(&'static u8), None }
+                union U { a: u16, b: [u8; 3] }
+                type Alias = Option<Box<S<'static>>>;
+                const VALUE: (u8, u32) = (1, 2);
+            }
+        });
+        let json = golden::to_json(&layouts);
+        let saved: Vec<layout::ItemLayout> = serde_json::from_str(&json).unwrap();
+        assert_eq!(saved, layouts);
+
+        let file = std::env::temp_dir().join(format!("pahole-golden-{}.json", std::process::id()));
+        std::fs::write(&file, &json).unwrap();
+        let lit = LitStr::new(file.to_str().unwrap(), proc_macro2::Span::call_site());
+        golden::check(&lit, &layouts).unwrap();
+        let changed = analyze(parse_quote! {
+            mod m {
+                struct S<'a> { a: u16, name: &'a str, b: [u16; 3] }
+                struct New;
+            }
+        });
+        let error = golden::check(&lit, &changed).unwrap_err().to_string();
+        std::fs::remove_file(&file).unwrap();
+        assert!(
+            error.ends_with(
+                "doesn't match; update it with `emit_json`:\n\
+             `m::S` changed\n\
+             `m::Aligned` is no longer analyzed\n\
+             `m::E` is no longer analyzed\n\
+             `m::Niche` is no longer analyzed\n\
+             `m::U` is no longer analyzed\n\
+             `m::Alias` is no longer analyzed\n\
+             `m::VALUE` is no longer analyzed\n\
+             `m::New` is not in the golden file"
+            ),
+            "{}",
+            error
+        );
+    }
+
+    #[cfg(not(feature = "serde"))]
+    #[test]
+    fn golden_files_need_the_serde_feature() {
+        let error = options::Options::parse(vec![parse_quote!(emit_json)]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "this option needs the `serde` feature of pahole-macro"
+        );
+    }
+
+    #[test]
+    fn heap_buffers_have_a_niche_per_invalid_capacity() {
+        let layouts = analyze(parse_quote! {
+            mod m {
+                struct Strings(Option<Option<String>>);
+                struct Vecs(Option<Option<Option<Vec<u8>>>>);
+            }
+        });
+        assert_eq!(layouts[0].total, Layout::new::<Option<Option<String>>>());
+        assert_eq!(
+            layouts[1].total,
+            Layout::new::<Option<Option<Option<Vec<u8>>>>>()
+        );
+    }
+}
@@ -0,0 +1,365 @@
+use syn::{parse::Error, AttributeArgs, Lit, LitInt, LitStr, Meta, NestedMeta};
+
+/// The order items are reported in.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SortOrder {
+    Name,
+    /// Largest first.
+    Size,
+    /// Most padding first.
+    Padding,
+}
+
+/// How the layout of each item is printed.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Format {
+    #[default]
+    Table,
+    /// One line per item and per field, for grepping and diffing.
+    Compact,
+}
+
+/// How to lay out types that pahole can't find, instead of failing.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum UnknownTypes {
+    Pointer,
+    /// Zero-sized, as if fields of the type weren't there.
+    Skip,
+    /// This many bytes, aligned to the largest power of two dividing it, up to a pointer's
+    /// alignment.
+    Size(usize),
+}
+
+/// A field to add to or remove from a struct, to report how its layout would change. Parsed from
+/// `"S.name: Type"` to add a field, or `"S.name"` to remove one, where `name` can be a tuple index.
+#[derive(Debug)]
+pub struct WhatIf {
+    pub lit: LitStr,
+    pub path: syn::TypePath,
+    pub name: String,
+    /// The type of the field to add, or `None` to remove it.
+    pub added: Option<syn::Type>,
+}
+
+impl WhatIf {
+    fn parse(lit: &LitStr, added: bool) -> Result<Self, Error> {
+        let expected = if added {
+            r#"expected a struct and a field to add, e.g. "S.flag: bool""#
+        } else {
+            r#"expected a struct and its field, e.g. "S.flag""#
+        };
+        let error = || Error::new_spanned(lit, expected);
+        let value = lit.value();
+        let (path, field) = value.split_once('.').ok_or_else(error)?;
+        let path = syn::parse_str(path.trim()).map_err(|_| error())?;
+        let (name, added) = if added {
+            let (name, ty) = field.split_once(':').ok_or_else(error)?;
+            (name, Some(syn::parse_str(ty).map_err(|_| error())?))
+        } else {
+            (field, None)
+        };
+        Ok(Self {
+            lit: lit.clone(),
+            path,
+            name: name.trim().to_string(),
+            added,
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Options {
+    pub histogram: bool,
+    pub bytemap: bool,
+    pub sizes: Option<LitStr>,
+    /// A file with the C layouts the structs must match, in the format of the sizes file.
+    pub c_layout: Option<LitStr>,
+    /// Overrides the host's pointer width, in bits.
+    pub pointer_width: Option<usize>,
+    /// Restricts the report to these items, if given.
+    pub only: Option<Vec<syn::Path>>,
+    /// The element count to compare arrays of structs and structs of arrays for.
+    pub aos_soa: Option<usize>,
+    /// The number of instances of each item to total the memory and padding of.
+    pub count: Option<usize>,
+    /// Instantiations of generic items to lay out too, e.g. `Buf<16>`.
+    pub monomorphize: Vec<syn::Type>,
+    /// The enabled features to evaluate `#[cfg(feature = "...")]` with, which proc macros can't
+    /// otherwise see.
+    pub features: Option<Vec<String>>,
+    /// Fields to report the effect of adding or removing, without changing the structs.
+    pub what_if: Vec<WhatIf>,
+    /// Adds a summary of each item's layout to its doc comment.
+    pub emit_docs: bool,
+    /// Lists groups of structs with the same layout.
+    pub layout_compatible: bool,
+    /// Lists the analyzed items each item embeds by value.
+    pub dependency_graph: bool,
+    pub format: Format,
+    /// Reorders the report, which otherwise follows declaration order.
+    pub sort: Option<SortOrder>,
+    /// The contents of a `repr` attribute to also lay out structs with, e.g. `"C"`.
+    pub as_repr: Option<LitStr>,
+    /// Names this invocation in the header of each report, instead of the item's path.
+    pub label: Option<String>,
+    /// The number of bits to list for flags newtypes like `struct Flags(u32)`.
+    pub flag_bits: Option<usize>,
+    /// The size the annotated item must have.
+    pub size: Option<LitInt>,
+    /// The alignment the annotated item must have.
+    pub align: Option<LitInt>,
+    pub unknown: Option<UnknownTypes>,
+    /// Names field types by the aliases they're declared with, rather than the aliased types.
+    pub keep_aliases: bool,
+    /// Emits a `layout_of_<path>!` macro for each item, to query its layout from other macros.
+    pub emit_table_macro: bool,
+    /// Prints pahole's internal representation of the items.
+    pub debug_parse: bool,
+    /// Emits a `<path>_layout` module for each item, with its layout as consts.
+    pub emit_mod: bool,
+    /// Reports how layouts depend on the pointer width, in terms of the size of a pointer, or with
+    /// the compact format, as a diff of the layouts for each pointer width.
+    pub symbolic_ptr: bool,
+    /// Fails the build if an item isn't FFI-safe.
+    pub require_ffi_safe: bool,
+    /// Reports the share of each struct's size its fields take.
+    pub field_percentages: bool,
+    /// Reports the size of each struct under `repr(packed)`, as a lower bound.
+    pub show_packed_baseline: bool,
+    /// Only knows the types from the `sizes` file, not even primitives.
+    pub no_builtins: bool,
+    /// A JSON file with the layouts the items must still have, from `emit_json`.
+    pub golden: Option<LitStr>,
+    /// Prints the layouts as JSON, for a `golden` file.
+    pub emit_json: bool,
+}
+
+impl Options {
+    pub fn parse(args: AttributeArgs) -> Result<Self, Error> {
+        let mut options = Self::default();
+        for arg in args {
+            match &arg {
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("histogram") => {
+                    options.histogram = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("bytemap") => {
+                    options.bytemap = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("emit_docs") => {
+                    options.emit_docs = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("emit_table_macro") => {
+                    options.emit_table_macro = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("debug_parse") => {
+                    options.debug_parse = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("emit_mod") => {
+                    options.emit_mod = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("show_packed_baseline") => {
+                    options.show_packed_baseline = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("symbolic_ptr") => {
+                    options.symbolic_ptr = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("field_percentages") => {
+                    options.field_percentages = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("require_ffi_safe") => {
+                    options.require_ffi_safe = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("no_builtins") => {
+                    options.no_builtins = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("layout_compatible") => {
+                    options.layout_compatible = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("dependency_graph") => {
+                    options.dependency_graph = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("emit_json") => {
+                    needs_serde(path)?;
+                    options.emit_json = true;
+                }
+                NestedMeta::Meta(Meta::NameValue(x)) if x.path.is_ident("sizes") => match &x.lit {
+                    Lit::Str(lit) => options.sizes = Some(lit.clone()),
+                    lit => return Err(Error::new_spanned(lit, "expected a file path")),
+                },
+                NestedMeta::Meta(Meta::NameValue(x)) if x.path.is_ident("c_layout") => {
+                    match &x.lit {
+                        Lit::Str(lit) => options.c_layout = Some(lit.clone()),
+                        lit => return Err(Error::new_spanned(lit, "expected a file path")),
+                    }
+                }
+                NestedMeta::Meta(Meta::NameValue(x)) if x.path.is_ident("golden") => {
+                    needs_serde(&x.path)?;
+                    match &x.lit {
+                        Lit::Str(lit) => options.golden = Some(lit.clone()),
+                        lit => return Err(Error::new_spanned(lit, "expected a file path")),
+                    }
+                }
+                NestedMeta::Meta(Meta::NameValue(x)) if x.path.is_ident("as_repr") => {
+                    match &x.lit {
+                        Lit::Str(lit) => options.as_repr = Some(lit.clone()),
+                        lit => {
+                            return Err(Error::new_spanned(lit, r#"expected a `repr`, e.g. "C""#))
+                        }
+                    }
+                }
+                NestedMeta::Meta(Meta::NameValue(x)) if x.path.is_ident("label") => match &x.lit {
+                    Lit::Str(lit) => options.label = Some(lit.value()),
+                    lit => return Err(Error::new_spanned(lit, "expected a string")),
+                },
+                NestedMeta::Meta(Meta::NameValue(x)) if x.path.is_ident("expand_aliases") => {
+                    match &x.lit {
+                        Lit::Bool(lit) => options.keep_aliases = !lit.value,
+                        lit => return Err(Error::new_spanned(lit, "expected `true` or `false`")),
+                    }
+                }
+                NestedMeta::Meta(Meta::NameValue(x)) if x.path.is_ident("count") => match &x.lit {
+                    Lit::Int(lit) => options.count = Some(lit.base10_parse()?),
+                    lit => return Err(Error::new_spanned(lit, "expected a number of instances")),
+                },
+                NestedMeta::Meta(Meta::NameValue(x)) if x.path.is_ident("flag_bits") => {
+                    match &x.lit {
+                        Lit::Int(lit) => options.flag_bits = Some(lit.base10_parse()?),
+                        lit => return Err(Error::new_spanned(lit, "expected a number of bits")),
+                    }
+                }
+                NestedMeta::Meta(Meta::NameValue(x))
+                    if x.path.is_ident("size") || x.path.is_ident("align") =>
+                {
+                    let lit = match &x.lit {
+                        Lit::Int(lit) => {
+                            lit.base10_parse::<usize>()?;
+                            Some(lit.clone())
+                        }
+                        lit => return Err(Error::new_spanned(lit, "expected a number of bytes")),
+                    };
+                    if x.path.is_ident("size") {
+                        options.size = lit;
+                    } else {
+                        options.align = lit;
+                    }
+                }
+                NestedMeta::Meta(Meta::NameValue(x)) if x.path.is_ident("unknown") => {
+                    options.unknown = Some(match &x.lit {
+                        Lit::Str(lit) if lit.value() == "pointer" => UnknownTypes::Pointer,
+                        Lit::Str(lit) if lit.value() == "skip" => UnknownTypes::Skip,
+                        Lit::Int(lit) => UnknownTypes::Size(lit.base10_parse()?),
+                        lit => {
+                            return Err(Error::new_spanned(
+                                lit,
+                                r#"expected "pointer", "skip", or a size in bytes"#,
+                            ))
+                        }
+                    })
+                }
+                NestedMeta::Meta(Meta::NameValue(x)) if x.path.is_ident("format") => {
+                    options.format = match &x.lit {
+                        Lit::Str(lit) if lit.value() == "table" => Format::Table,
+                        Lit::Str(lit) if lit.value() == "compact" => Format::Compact,
+                        lit => {
+                            return Err(Error::new_spanned(lit, r#"expected "table" or "compact""#))
+                        }
+                    }
+                }
+                NestedMeta::Meta(Meta::NameValue(x)) if x.path.is_ident("sort") => {
+                    options.sort = Some(match &x.lit {
+                        Lit::Str(lit) if lit.value() == "name" => SortOrder::Name,
+                        Lit::Str(lit) if lit.value() == "size" => SortOrder::Size,
+                        Lit::Str(lit) if lit.value() == "padding" => SortOrder::Padding,
+                        lit => {
+                            return Err(Error::new_spanned(
+                                lit,
+                                r#"expected "name", "size", or "padding""#,
+                            ))
+                        }
+                    })
+                }
+                NestedMeta::Meta(Meta::NameValue(x)) if x.path.is_ident("pointer_width") => {
+                    match &x.lit {
+                        Lit::Int(lit) if matches!(lit.base10_parse(), Ok(16) | Ok(32) | Ok(64)) => {
+                            options.pointer_width = Some(lit.base10_parse()?)
+                        }
+                        lit => return Err(Error::new_spanned(lit, "expected 16, 32, or 64")),
+                    }
+                }
+                NestedMeta::Meta(Meta::List(x)) if x.path.is_ident("only") => {
+                    let only = options.only.get_or_insert_with(Vec::new);
+                    for nested in &x.nested {
+                        match nested {
+                            NestedMeta::Meta(Meta::Path(path)) => only.push(path.clone()),
+                            _ => return Err(Error::new_spanned(nested, "expected an item name")),
+                        }
+                    }
+                }
+                NestedMeta::Meta(Meta::List(x)) if x.path.is_ident("features") => {
+                    let features = options.features.get_or_insert_with(Vec::new);
+                    for nested in &x.nested {
+                        match nested {
+                            NestedMeta::Lit(Lit::Str(lit)) => features.push(lit.value()),
+                            _ => {
+                                return Err(Error::new_spanned(
+                                    nested,
+                                    "expected a quoted feature name",
+                                ))
+                            }
+                        }
+                    }
+                }
+                NestedMeta::Meta(Meta::List(x)) if x.path.is_ident("monomorphize") => {
+                    for nested in &x.nested {
+                        match nested {
+                            NestedMeta::Lit(Lit::Str(lit)) => {
+                                options.monomorphize.push(lit.parse()?)
+                            }
+                            _ => {
+                                return Err(Error::new_spanned(
+                                    nested,
+                                    r#"expected a quoted instantiation, e.g. "Buf<16>""#,
+                                ))
+                            }
+                        }
+                    }
+                }
+                NestedMeta::Meta(Meta::List(x))
+                    if x.path.is_ident("what_if_added") || x.path.is_ident("what_if_removed") =>
+                {
+                    let added = x.path.is_ident("what_if_added");
+                    for nested in &x.nested {
+                        match nested {
+                            NestedMeta::Lit(Lit::Str(lit)) => {
+                                options.what_if.push(WhatIf::parse(lit, added)?)
+                            }
+                            _ => return Err(Error::new_spanned(nested, "expected a string")),
+                        }
+                    }
+                }
+                NestedMeta::Meta(Meta::List(x)) if x.path.is_ident("aos_soa") => {
+                    match x.nested.iter().collect::<Vec<_>>().as_slice() {
+                        [NestedMeta::Lit(Lit::Int(lit))] => {
+                            options.aos_soa = Some(lit.base10_parse()?)
+                        }
+                        _ => return Err(Error::new_spanned(x, "expected an element count")),
+                    }
+                }
+                _ => return Err(Error::new_spanned(arg, "unknown pahole option")),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Errors on an option reading or writing golden files unless the `serde` feature is enabled.
+fn needs_serde(option: &syn::Path) -> Result<(), Error> {
+    if cfg!(feature = "serde") {
+        Ok(())
+    } else {
+        Err(Error::new_spanned(
+            option,
+            "this option needs the `serde` feature of pahole-macro",
+        ))
+    }
+}
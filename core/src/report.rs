@@ -0,0 +1,1123 @@
+use std::{
+    alloc::Layout,
+    collections::{HashMap, HashSet},
+    fmt::Write,
+};
+
+use quote::ToTokens;
+
+use crate::{
+    layout::{same_layout, Discriminant, FieldLayout, ItemLayout, Shape, StructLayout, Target},
+    options::SortOrder,
+    parsed,
+    what_if::LayoutDelta,
+};
+
+/// Renders tokens the way they would usually be written, e.g. `Vec<u8>` instead of `Vec < u8 >`.
+pub fn tokens_to_string(tokens: &impl ToTokens) -> String {
+    let mut s = tokens.to_token_stream().to_string();
+    for (from, to) in &[
+        (" :: ", "::"),
+        (":: ", "::"),
+        (" : ", ": "),
+        (" < ", "<"),
+        ("< ", "<"),
+        (" >", ">"),
+        (" ,", ","),
+        (" ;", ";"),
+        ("& ", "&"),
+        ("[ ", "["),
+        (" ]", "]"),
+        ("( ", "("),
+        (" )", ")"),
+    ] {
+        s = s.replace(from, to);
+    }
+    s
+}
+
+/// Like `tokens_to_string`, but also tightens attributes and visibilities like `pub(crate)`.
+fn compact_tokens_to_string(tokens: &impl ToTokens) -> String {
+    tokens_to_string(tokens)
+        .replace("# [", "#[")
+        .replace(" (", "(")
+}
+
+fn write_attrs(out: &mut String, attrs: &[syn::Attribute], indent: &str) {
+    for attr in attrs {
+        writeln!(out, "{}{}", indent, compact_tokens_to_string(attr)).unwrap();
+    }
+}
+
+fn with_vis(vis: &syn::Visibility, rest: String) -> String {
+    match vis {
+        syn::Visibility::Inherited => rest,
+        _ => format!("{} {}", compact_tokens_to_string(vis), rest),
+    }
+}
+
+/// Renders `definition` with its fields in `order`, keeping attributes and visibilities.
+pub fn struct_snippet(definition: &syn::ItemStruct, order: &[usize]) -> String {
+    let fields: Vec<&syn::Field> = definition.fields.iter().collect();
+    let mut out = String::new();
+    write_attrs(&mut out, &definition.attrs, "");
+    let header = format!(
+        "struct {}{}",
+        definition.ident,
+        tokens_to_string(&definition.generics)
+    );
+    let where_clause = match &definition.generics.where_clause {
+        Some(x) => format!(" {}", tokens_to_string(x)),
+        None => String::new(),
+    };
+    let named = matches!(definition.fields, syn::Fields::Named(_));
+    let header = with_vis(&definition.vis, header);
+    if named {
+        writeln!(out, "{}{} {{", header, where_clause).unwrap();
+    } else {
+        writeln!(out, "{}(", header).unwrap();
+    }
+    for &i in order {
+        let field = fields[i];
+        write_attrs(&mut out, &field.attrs, "    ");
+        let ty = tokens_to_string(&field.ty);
+        let rest = match &field.ident {
+            Some(ident) => format!("{}: {}", ident, ty),
+            None => ty,
+        };
+        writeln!(out, "    {},", with_vis(&field.vis, rest)).unwrap();
+    }
+    if named {
+        writeln!(out, "}}").unwrap();
+    } else {
+        writeln!(out, "){};", where_clause).unwrap();
+    }
+    out
+}
+
+/// Suggests a field order for a struct with less padding, if there is one.
+pub fn reordering(item: &ItemLayout, definition: &syn::ItemStruct) -> String {
+    let layout = match &item.shape {
+        Shape::Struct(x) => x,
+        _ => return String::new(),
+    };
+    let (order, reordered) = match layout.reordered() {
+        Some(x) => x,
+        None => return String::new(),
+    };
+    let mut out = String::new();
+    writeln!(
+        out,
+        "reordering fields would save {} bytes ({} -> {}):",
+        layout.total.size() - reordered.total.size(),
+        layout.total.size(),
+        reordered.total.size()
+    )
+    .unwrap();
+    for line in struct_snippet(definition, &order).lines() {
+        writeln!(out, "    {}", line).unwrap();
+    }
+    out
+}
+
+/// Points out a struct whose fields are declared in strictly ascending order of alignment, if
+/// reversing them would make it smaller.
+pub fn ascending_alignment(item: &ItemLayout) -> String {
+    let layout = match &item.shape {
+        Shape::Struct(x) if !x.unsized_tail && !x.flexible_array && x.hole_bytes() != 0 => x,
+        _ => return String::new(),
+    };
+    let ascending = layout.fields.len() >= 2
+        && layout
+            .fields
+            .windows(2)
+            .all(|x| x[0].layout.align() < x[1].layout.align());
+    if !ascending {
+        return String::new();
+    }
+    let reversed: Vec<_> = layout
+        .fields
+        .iter()
+        .rev()
+        .map(|x| (x.name.clone(), x.ty.clone(), x.layout))
+        .collect();
+    let names: Vec<String> = reversed.iter().map(|x| format!("`{}`", x.0)).collect();
+    let reversed = StructLayout::sequential(Layout::new::<()>(), reversed);
+    if reversed.total.size() >= layout.total.size() {
+        return String::new();
+    }
+    format!(
+        "warning: the fields of `{}` are declared in ascending order of alignment; reverse them to \
+         {} to have {} bytes of holes instead of {} (size {} -> {})\n",
+        item.path,
+        names.join(", "),
+        reversed.hole_bytes(),
+        layout.hole_bytes(),
+        layout.total.size(),
+        reversed.total.size()
+    )
+}
+
+/// A one-line summary of an item's layout, e.g. `Layout: size=16, align=8, 1 hole`.
+pub fn doc_summary(item: &ItemLayout) -> String {
+    let mut out = format!(
+        "Layout: size={}, align={}",
+        item.total.size(),
+        item.total.align()
+    );
+    if let Shape::Struct(x) = &item.shape {
+        let holes = x.holes.len();
+        write!(out, ", {} hole{}", holes, if holes == 1 { "" } else { "s" }).unwrap();
+    }
+    out
+}
+
+/// Warns about the fields of a `repr(packed(pack))` struct that need more alignment than the
+/// packing guarantees, since creating references to them is undefined behavior.
+pub fn packed_references(item: &ItemLayout, pack: usize) -> String {
+    let layout = match &item.shape {
+        Shape::Struct(x) => x,
+        _ => return String::new(),
+    };
+    let mut out = String::new();
+    for field in layout.fields.iter().filter(|x| x.layout.align() > pack) {
+        writeln!(
+            out,
+            "warning: field `{}` needs alignment {} but packing only guarantees {}, so references \
+             to it are undefined behavior; copy it out instead",
+            field.name,
+            field.layout.align(),
+            pack
+        )
+        .unwrap();
+    }
+    out
+}
+
+const SMALL_INTEGERS: &[&str] = &["u8", "i8", "u16", "i16"];
+
+/// Notes the `bool` fields of a `repr(C)` struct, which match C's `_Bool`.
+pub fn bool_note(item: &ItemLayout) -> String {
+    let fields: Vec<String> = match &item.shape {
+        Shape::Struct(x) => x
+            .fields
+            .iter()
+            .filter(|y| tokens_to_string(&y.ty) == "bool")
+            .map(|y| format!("`{}`", y.name))
+            .collect(),
+        _ => return String::new(),
+    };
+    if fields.is_empty() {
+        return String::new();
+    }
+    format!(
+        "note: `bool` field{} {} match{} C's `_Bool`, one byte holding 0 or 1\n",
+        if fields.len() == 1 { "" } else { "s" },
+        fields.join(", "),
+        if fields.len() == 1 { "es" } else { "" }
+    )
+}
+
+/// Notes runs of adjacent fields with the same small integer type, which in a `repr(C)` struct
+/// often stand in for C bitfields, which Rust can't express and which are packed differently.
+pub fn bitfield_note(item: &ItemLayout) -> String {
+    let layout = match &item.shape {
+        Shape::Struct(x) => x,
+        _ => return String::new(),
+    };
+    let mut out = String::new();
+    let mut fields = layout.fields.iter().peekable();
+    while let Some(first) = fields.next() {
+        let ty = tokens_to_string(&first.ty);
+        if !SMALL_INTEGERS.contains(&ty.as_str()) {
+            continue;
+        }
+        let mut run = vec![format!("`{}`", first.name)];
+        while let Some(field) = fields.next_if(|x| tokens_to_string(&x.ty) == ty) {
+            run.push(format!("`{}`", field.name));
+        }
+        if run.len() > 1 {
+            writeln!(
+                out,
+                "note: fields {} ({}) could be C bitfields; a C struct declaring them as bitfields \
+                 would have a different layout",
+                run.join(", "),
+                ty
+            )
+            .unwrap();
+        }
+    }
+    out
+}
+
+/// Points out small fields like flags that are scattered between larger fields, leaving holes.
+pub fn small_field_grouping(item: &ItemLayout) -> String {
+    let layout = match &item.shape {
+        Shape::Struct(x) => x,
+        _ => return String::new(),
+    };
+    let (scattered, grouped) = match layout.grouped_small_fields() {
+        Some(x) => x,
+        None => return String::new(),
+    };
+    let names: Vec<String> = scattered
+        .iter()
+        .map(|&i| format!("`{}`", layout.fields[i].name))
+        .collect();
+    if grouped.total.size() == layout.total.size() {
+        // Too few small fields to fill a whole hole: grouping them only moves the bytes to the end.
+        let wasted: usize = scattered
+            .iter()
+            .map(|&i| {
+                let end = layout.fields[i].offset + layout.fields[i].layout.size();
+                layout
+                    .holes
+                    .iter()
+                    .find(|x| x.offset == end)
+                    .map_or(0, |x| x.size)
+            })
+            .sum();
+        return format!(
+            "small fields {} are followed by {} bytes of holes; grouping small fields after the \
+             larger ones would keep the size of {}, but leave room for more small fields\n",
+            names.join(", "),
+            wasted,
+            layout.total.size()
+        );
+    }
+    format!(
+        "small fields {} are followed by holes; grouping small fields after the larger ones would \
+         remove {} bytes of holes (size {} -> {})\n",
+        names.join(", "),
+        layout.hole_bytes() - grouped.hole_bytes(),
+        layout.total.size(),
+        grouped.total.size()
+    )
+}
+
+/// Suggests moving single fields into earlier holes they fit in.
+pub fn hole_fillings(item: &ItemLayout) -> String {
+    let layout = match &item.shape {
+        Shape::Struct(x) => x,
+        _ => return String::new(),
+    };
+    let mut out = String::new();
+    for (field, before, filled) in layout.hole_fillings() {
+        let moved = &layout.fields[field];
+        let before = &layout.fields[before];
+        let hole = layout
+            .holes
+            .iter()
+            .find(|x| x.offset == before.offset + before.layout.size())
+            .unwrap();
+        writeln!(
+            out,
+            "move `{}` ({} byte{}) into the {}-byte hole after `{}` (size {} -> {})",
+            moved.name,
+            moved.layout.size(),
+            if moved.layout.size() == 1 { "" } else { "s" },
+            hole.size,
+            before.name,
+            layout.total.size(),
+            filled.total.size()
+        )
+        .unwrap();
+    }
+    out
+}
+
+const HEADER: &str = "  offset  size  align  field";
+
+/// Writes a row of the field table, leaving the alignment blank for bytes that aren't a field.
+fn row(out: &mut String, offset: usize, size: usize, align: Option<usize>, label: &str) {
+    let align = align.map_or_else(String::new, |x| x.to_string());
+    writeln!(out, "  {:>6}  {:>4}  {:>5}  {}", offset, size, align, label).unwrap();
+}
+
+fn struct_table(out: &mut String, layout: &StructLayout, tag_size: Option<usize>) {
+    writeln!(out, "{}", HEADER).unwrap();
+    if let Some(size) = tag_size {
+        row(out, 0, size, Some(size), "<tag>");
+    }
+    let mut holes = layout.holes.iter().peekable();
+    for field in &layout.fields {
+        while let Some(hole) = holes.next_if(|x| x.offset < field.offset) {
+            let label = format!(
+                "<hole> (`{}` is aligned to {})",
+                field.name,
+                field.layout.align()
+            );
+            row(out, hole.offset, hole.size, None, &label);
+        }
+        row(
+            out,
+            field.offset,
+            field.layout.size(),
+            Some(field.layout.align()),
+            &format!("{}: {}", field.name, tokens_to_string(&field.ty)),
+        );
+    }
+    if layout.padding != 0 {
+        row(
+            out,
+            layout.total.size() - layout.padding,
+            layout.padding,
+            None,
+            "<padding>",
+        );
+    }
+    for field in &layout.fields {
+        let inner = match &field.ty {
+            syn::Type::Path(x) => crate::generic_argument(x, "Option"),
+            _ => None,
+        };
+        if inner.is_some_and(crate::is_non_null_pointer) {
+            writeln!(
+                out,
+                "  field `{}` stores `None` as a null pointer",
+                field.name
+            )
+            .unwrap();
+        }
+    }
+    for field in layout.fields.iter().filter(|x| x.internal_padding != 0) {
+        write!(
+            out,
+            "  field `{}` contains {} bytes of internal padding",
+            field.name, field.internal_padding
+        )
+        .unwrap();
+        let len = match &field.ty {
+            syn::Type::Array(x) => crate::array_len(&x.len).ok(),
+            _ => None,
+        };
+        match len {
+            Some(len) if len > 1 && field.internal_padding % len == 0 => writeln!(
+                out,
+                " ({} in each of {} elements)",
+                field.internal_padding / len,
+                len
+            ),
+            _ => writeln!(out),
+        }
+        .unwrap();
+    }
+}
+
+/// The fields of `item`, prefixed as in `compact`, and the number of holes.
+fn prefixed_fields(item: &ItemLayout) -> (Vec<(String, &FieldLayout)>, usize) {
+    let mut fields: Vec<(String, &FieldLayout)> = Vec::new();
+    let mut holes = 0;
+    match &item.shape {
+        Shape::Struct(x) => {
+            holes = x.holes.len();
+            fields.extend(x.fields.iter().map(|y| (item.path.to_string(), y)));
+        }
+        Shape::Enum { variants, .. } => {
+            for variant in variants {
+                holes += variant.payload.holes.len();
+                let prefix = format!("{}::{}", item.path, variant.name);
+                fields.extend(variant.payload.fields.iter().map(|y| (prefix.clone(), y)));
+            }
+        }
+        Shape::Union(x) => fields.extend(x.iter().map(|y| (item.path.to_string(), y))),
+        Shape::TypeAlias(_) | Shape::Value(_) => {}
+    }
+    (fields, holes)
+}
+
+/// The layout of `item` as one line, then a line per field prefixed by the item's path and the
+/// field's name, e.g. `A: size=16 align=8 pad=7 holes=1` and `A.c @8 size=1 align=1`. Variants'
+/// fields are prefixed by the variant too, e.g. `E::B.0`.
+pub fn compact(item: &ItemLayout) -> String {
+    let (fields, holes) = prefixed_fields(item);
+    let mut out = String::new();
+    writeln!(
+        out,
+        "{}: size={} align={} pad={} holes={}",
+        item.path,
+        item.total.size(),
+        item.total.align(),
+        item.padding(),
+        holes
+    )
+    .unwrap();
+    for (prefix, field) in fields {
+        writeln!(
+            out,
+            "{}.{} @{} size={} align={}",
+            prefix,
+            field.name,
+            field.offset,
+            field.layout.size(),
+            field.layout.align()
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// Like `compact`, but for the layouts of an item for each of `Target::POINTER_WIDTHS`, with the
+/// values for each width separated by `/` and each line ending in `same` or `differs`, e.g.
+/// `A.len @0/0/0 size=2/4/8 differs`. Fields missing for some width are left out.
+pub fn compact_target_diff(items: &[&ItemLayout]) -> String {
+    let per_width: Vec<_> = items.iter().map(|x| prefixed_fields(x).0).collect();
+    let mut out = String::new();
+    let mut line = |label: String, values: &[(&str, Vec<usize>)]| {
+        let differs = values.iter().any(|(_, x)| x.iter().any(|y| *y != x[0]));
+        out.push_str(&label);
+        for (name, values) in values {
+            let values: Vec<String> = values.iter().map(|x| x.to_string()).collect();
+            write!(out, " {}{}", name, values.join("/")).unwrap();
+        }
+        writeln!(out, " {}", if differs { "differs" } else { "same" }).unwrap();
+    };
+    line(
+        format!("{}:", items[0].path),
+        &[
+            ("size=", items.iter().map(|x| x.total.size()).collect()),
+            ("align=", items.iter().map(|x| x.total.align()).collect()),
+        ],
+    );
+    for (prefix, field) in &per_width[0] {
+        let fields: Option<Vec<&FieldLayout>> = per_width
+            .iter()
+            .map(|x| {
+                x.iter()
+                    .find(|(y, z)| y == prefix && z.name == field.name)
+                    .map(|(_, z)| *z)
+            })
+            .collect();
+        if let Some(fields) = fields {
+            line(
+                format!("{}.{}", prefix, field.name),
+                &[
+                    ("@", fields.iter().map(|x| x.offset).collect()),
+                    ("size=", fields.iter().map(|x| x.layout.size()).collect()),
+                    ("align=", fields.iter().map(|x| x.layout.align()).collect()),
+                ],
+            );
+        }
+    }
+    out
+}
+
+/// Sorts items for the report. The sort is stable, so ties stay in declaration order.
+pub fn sort(items: &mut [ItemLayout], order: SortOrder) {
+    match order {
+        SortOrder::Name => items.sort_by_cached_key(|x| x.path.to_string()),
+        SortOrder::Size => items.sort_by_key(|x| std::cmp::Reverse(x.total.size())),
+        SortOrder::Padding => items.sort_by_key(|x| std::cmp::Reverse(x.padding())),
+    }
+}
+
+/// The line starting each item's report, naming `label`, or the item if there's no label, so
+/// that reports collected from many invocations can be told apart.
+pub fn header(item: &ItemLayout, label: Option<&str>) -> String {
+    match label {
+        Some(label) => format!("== {} ==\n", label),
+        None => format!("== {} ==\n", item.path),
+    }
+}
+
+pub fn table(item: &ItemLayout) -> String {
+    let mut out = String::new();
+    writeln!(
+        out,
+        "{}: size {}, align {}",
+        item.path,
+        item.total.size(),
+        item.total.align()
+    )
+    .unwrap();
+    if let Some((natural, reason)) = item.natural_align {
+        writeln!(
+            out,
+            "natural alignment {}, effective alignment {} ({})",
+            natural,
+            item.total.align(),
+            reason
+        )
+        .unwrap();
+    }
+    if !matches!(item.shape, Shape::TypeAlias(_) | Shape::Value(_)) {
+        match &item.ffi_unsafe {
+            Some(reason) => writeln!(out, "FFI-safe: no ({})", reason),
+            None => writeln!(out, "FFI-safe: yes"),
+        }
+        .unwrap();
+        match &item.zero_invalid {
+            Some(reason) => writeln!(out, "zero-initializable: no ({})", reason),
+            None => writeln!(out, "zero-initializable: yes"),
+        }
+        .unwrap();
+        if let Some(reason) = &item.drop_glue {
+            writeln!(out, "has drop glue ({})", reason).unwrap();
+        }
+    }
+    match &item.shape {
+        Shape::Struct(layout) => {
+            if layout.unsized_tail {
+                writeln!(out, "the last field is unsized; the size excludes it").unwrap();
+            }
+            if let Some(field) = layout.fields.last().filter(|_| layout.flexible_array) {
+                writeln!(
+                    out,
+                    "`{}` is a flexible array member starting at offset {}; the size excludes it",
+                    field.name, field.offset
+                )
+                .unwrap();
+            }
+            struct_table(&mut out, layout, None)
+        }
+        Shape::Enum {
+            discriminant,
+            variants,
+        } => {
+            let tag_size = match discriminant {
+                Discriminant::Tag(tag) => {
+                    let size = tag.layout.size();
+                    let unit = if size == 1 { "byte" } else { "bytes" };
+                    writeln!(out, "discriminant type: {} ({} {})", tag.name(), size, unit).unwrap();
+                    Some(size)
+                }
+                Discriminant::Niche { variant, field } => {
+                    writeln!(
+                        out,
+                        "discriminant stored in niche of field `{}` of variant `{}`",
+                        field, variant
+                    )
+                    .unwrap();
+                    None
+                }
+                Discriminant::None => None,
+            };
+            for variant in variants {
+                if tag_size.is_some() {
+                    writeln!(
+                        out,
+                        "variant {} (discriminant {}):",
+                        variant.name, variant.discriminant
+                    )
+                } else {
+                    writeln!(out, "variant {}:", variant.name)
+                }
+                .unwrap();
+                struct_table(&mut out, &variant.payload, tag_size);
+            }
+            let mut values: Vec<i128> = variants.iter().map(|x| x.discriminant).collect();
+            values.sort_unstable();
+            if tag_size.is_some() && values.windows(2).any(|x| x[1] - x[0] > 1) {
+                writeln!(out, "the gaps between discriminants don't affect the size").unwrap();
+            }
+            for variant in variants.iter().filter(|x| x.packed_tag) {
+                writeln!(
+                    out,
+                    "discriminant packed into payload padding of variant `{}`",
+                    variant.name
+                )
+                .unwrap();
+            }
+        }
+        Shape::Union(fields) => {
+            writeln!(out, "{}", HEADER).unwrap();
+            for field in fields {
+                row(
+                    &mut out,
+                    field.offset,
+                    field.layout.size(),
+                    Some(field.layout.align()),
+                    &format!("{}: {}", field.name, tokens_to_string(&field.ty)),
+                );
+            }
+            for field in fields {
+                let inner = match &field.ty {
+                    syn::Type::Path(x) => crate::generic_argument(x, "ManuallyDrop"),
+                    _ => None,
+                };
+                if let Some(inner) = inner {
+                    writeln!(
+                        out,
+                        "  `{}` is `ManuallyDrop`, so it has the layout of `{}`",
+                        field.name,
+                        tokens_to_string(inner)
+                    )
+                    .unwrap();
+                }
+            }
+        }
+        Shape::TypeAlias(ty) => writeln!(out, "  = {}", tokens_to_string(ty)).unwrap(),
+        Shape::Value(ty) => writeln!(out, "  : {}", tokens_to_string(ty)).unwrap(),
+    }
+    out
+}
+
+/// Points out that some layouts came from `std_layouts` rather than the definitions.
+pub fn std_layouts_note(used: &HashSet<parsed::TypePath>) -> String {
+    if used.is_empty() {
+        return String::new();
+    }
+    let mut names: Vec<String> = used.iter().map(|x| format!("`{}`", x)).collect();
+    names.sort();
+    format!(
+        "note: the layouts of {} are those of the current standard library and may differ in other Rust versions\n",
+        names.join(", ")
+    )
+}
+
+pub const CACHE_LINE_SIZE: usize = 64;
+
+/// For structs larger than a cache line, says how many leading fields fit entirely in the first
+/// one, which is where a struct could be split into hot and cold parts.
+pub fn hot_prefix(item: &ItemLayout) -> String {
+    let layout = match &item.shape {
+        Shape::Struct(x) if x.total.size() > CACHE_LINE_SIZE => x,
+        _ => return String::new(),
+    };
+    let prefix: Vec<_> = layout
+        .fields
+        .iter()
+        .take_while(|x| x.offset + x.layout.size() <= CACHE_LINE_SIZE)
+        .collect();
+    let bytes = prefix.last().map_or(0, |x| x.offset + x.layout.size());
+    format!(
+        "first {} fields ({} bytes) fit in cache line 0\n",
+        prefix.len(),
+        bytes
+    )
+}
+
+/// Compares `count` structs stored as an array with one array per field, which has no padding.
+pub fn aos_soa(item: &ItemLayout, count: usize) -> String {
+    let layout = match &item.shape {
+        Shape::Struct(x) => x,
+        _ => return String::new(),
+    };
+    let aos = layout.total.size() * count;
+    let padding = layout.holes.iter().map(|x| x.size).sum::<usize>() + layout.padding;
+    let soa: usize = layout.fields.iter().map(|x| x.layout.size() * count).sum();
+    let mut out = String::new();
+    writeln!(
+        out,
+        "array of {} structs: {} bytes ({} bytes of padding)",
+        count,
+        aos,
+        padding * count
+    )
+    .unwrap();
+    writeln!(out, "struct of {}-element arrays: {} bytes", count, soa).unwrap();
+    writeln!(out, "splitting into arrays would save {} bytes", aos - soa).unwrap();
+    out
+}
+
+/// `bytes`, with the number of KiB too when it's at least one.
+fn bytes_and_kib(bytes: usize) -> String {
+    if bytes < 1024 {
+        format!("{} bytes", bytes)
+    } else {
+        format!("{} bytes ({:.1} KiB)", bytes, bytes as f64 / 1024.0)
+    }
+}
+
+/// The memory `count` values of `item` take, and how much of it is padding.
+pub fn instance_totals(item: &ItemLayout, count: usize) -> String {
+    format!(
+        "{} instances of `{}` take {}, wasting {} on padding\n",
+        count,
+        item.path,
+        bytes_and_kib(item.total.size() * count),
+        bytes_and_kib(item.padding() * count)
+    )
+}
+
+/// The size of a struct under `repr(packed)`, the sum of its field sizes, as a lower bound on the
+/// space its data needs.
+pub fn packed_baseline(item: &ItemLayout) -> String {
+    let layout = match &item.shape {
+        Shape::Struct(x) => x,
+        _ => return String::new(),
+    };
+    let packed: usize = layout.fields.iter().map(|x| x.layout.size()).sum();
+    format!(
+        "packed baseline: {} bytes without any padding, {} fewer than the actual size\n",
+        packed,
+        item.total.size() - packed
+    )
+}
+
+/// Renders `values`, measured for each of `Target::POINTER_WIDTHS`, as `a + b * ptr` if they fit
+/// that formula, or lists them otherwise.
+fn symbolic_bytes(values: &[usize]) -> String {
+    let ptr = |i: usize| (Target::POINTER_WIDTHS[i] / 8) as i64;
+    let value = |i: usize| values[i] as i64;
+    let last = values.len() - 1;
+    let (delta, step) = (value(last) - value(0), ptr(last) - ptr(0));
+    let per_ptr = delta / step;
+    let constant = value(0) - per_ptr * ptr(0);
+    let fits = delta % step == 0
+        && per_ptr >= 0
+        && constant >= 0
+        && (0..values.len()).all(|i| value(i) == constant + per_ptr * ptr(i));
+    if !fits {
+        let values: Vec<String> = Target::POINTER_WIDTHS
+            .iter()
+            .zip(values)
+            .map(|(width, value)| format!("{} with {}-bit pointers", value, width))
+            .collect();
+        return values.join(", ");
+    }
+    match (constant, per_ptr) {
+        (constant, 0) => constant.to_string(),
+        (0, 1) => "ptr".to_string(),
+        (0, per_ptr) => format!("{} * ptr", per_ptr),
+        (constant, 1) => format!("{} + ptr", constant),
+        (constant, per_ptr) => format!("{} + {} * ptr", constant, per_ptr),
+    }
+}
+
+/// How the layout of an item depends on the pointer width, in terms of `ptr`, the size of a
+/// pointer, given its layouts for each of `Target::POINTER_WIDTHS`. Empty if it doesn't.
+pub fn symbolic(items: &[&ItemLayout]) -> String {
+    let sizes: Vec<_> = items.iter().map(|x| x.total.size()).collect();
+    let aligns: Vec<_> = items.iter().map(|x| x.total.align()).collect();
+    if items.iter().all(|x| x.total == items[0].total) {
+        return String::new();
+    }
+    let mut out = format!(
+        "`{}` depends on the pointer width: size {}, align {}\n",
+        items[0].path,
+        symbolic_bytes(&sizes),
+        symbolic_bytes(&aligns)
+    );
+    if let Shape::Struct(first) = &items[0].shape {
+        for field in &first.fields {
+            let fields: Option<Vec<&FieldLayout>> = items
+                .iter()
+                .map(|x| match &x.shape {
+                    Shape::Struct(x) => x.fields.iter().find(|y| y.name == field.name),
+                    _ => None,
+                })
+                .collect();
+            if let Some(fields) = fields {
+                let offsets: Vec<_> = fields.iter().map(|x| x.offset).collect();
+                let sizes: Vec<_> = fields.iter().map(|x| x.layout.size()).collect();
+                writeln!(
+                    out,
+                    "  field `{}`: offset {}, size {}",
+                    field.name,
+                    symbolic_bytes(&offsets),
+                    symbolic_bytes(&sizes)
+                )
+                .unwrap();
+            }
+        }
+    }
+    out
+}
+
+/// The share of a struct's size each field takes, counting holes toward the field after them, so
+/// that the shares and the tail padding add up to the whole size.
+pub fn field_percentages(item: &ItemLayout) -> String {
+    let layout = match &item.shape {
+        Shape::Struct(x) if item.total.size() != 0 => x,
+        _ => return String::new(),
+    };
+    let percent = |bytes: usize| 100.0 * bytes as f64 / item.total.size() as f64;
+    let mut fields: Vec<&FieldLayout> = layout.fields.iter().collect();
+    fields.sort_by_key(|x| x.offset);
+    let mut out = format!(
+        "shares of the {} bytes of `{}`, counting holes toward the field after them:\n",
+        item.total.size(),
+        item.path
+    );
+    let mut end = 0;
+    for field in fields {
+        let bytes = field.offset + field.layout.size() - end.min(field.offset);
+        writeln!(out, "  {:>5.1}%  {}", percent(bytes), field.name).unwrap();
+        end = end.max(field.offset + field.layout.size());
+    }
+    if layout.padding != 0 {
+        writeln!(out, "  {:>5.1}%  <padding>", percent(layout.padding)).unwrap();
+    }
+    out
+}
+
+const ARRAY_SAMPLE_LEN: usize = 1000;
+
+/// Points out that arrays repeat a struct's tail padding, or the bytes an enum's variants leave
+/// unused, in every element.
+pub fn array_waste(item: &ItemLayout) -> String {
+    let (waste, what) = match &item.shape {
+        Shape::Struct(x) => (x.padding, "tail-padding bytes"),
+        Shape::Enum { .. } => (item.padding(), "bytes unused by any variant"),
+        _ => return String::new(),
+    };
+    if waste == 0 {
+        return String::new();
+    }
+    format!(
+        "each array element wastes {} {}; a [{}; {}] wastes {}\n",
+        waste,
+        what,
+        item.path,
+        ARRAY_SAMPLE_LEN,
+        waste * ARRAY_SAMPLE_LEN
+    )
+}
+
+/// Compares each boxed field of `item` with storing the boxed item inline, given the boxed
+/// fields' indices, the boxed items' layouts, and the layouts `item` would have with them inline.
+pub fn boxed_fields(item: &ItemLayout, boxed: &[(usize, Layout, StructLayout)]) -> String {
+    let fields = match &item.shape {
+        Shape::Struct(x) => &x.fields,
+        _ => return String::new(),
+    };
+    let mut out = String::new();
+    for (i, inline, inlined) in boxed {
+        let field = &fields[*i];
+        writeln!(
+            out,
+            "field `{}: {}`: {} bytes (inline would be {} bytes, aligned to {}, making `{}` {} bytes instead of {})",
+            field.name,
+            tokens_to_string(&field.ty),
+            field.layout.size(),
+            inline.size(),
+            inline.align(),
+            item.path,
+            inlined.total.size(),
+            item.total.size()
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// The fields of the unions stored in fields of `item`, at their offsets within `item`, indented
+/// under the field holding the union.
+pub fn union_fields(item: &ItemLayout, unions: &[(usize, Vec<FieldLayout>)]) -> String {
+    let fields = match &item.shape {
+        Shape::Struct(x) => &x.fields,
+        _ => return String::new(),
+    };
+    let mut out = String::new();
+    for (i, union_fields) in unions {
+        let field = &fields[*i];
+        writeln!(
+            out,
+            "field `{}: {}` is a union whose fields overlap:",
+            field.name,
+            tokens_to_string(&field.ty)
+        )
+        .unwrap();
+        for x in union_fields {
+            row(
+                &mut out,
+                field.offset + x.offset,
+                x.layout.size(),
+                Some(x.layout.align()),
+                &format!("  {}: {}", x.name, tokens_to_string(&x.ty)),
+            );
+        }
+    }
+    out
+}
+
+const INTEGERS: &[&str] = &[
+    "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128", "isize",
+];
+
+/// Lists the first `bits` bits of a flags newtype like `struct Flags(u32)`, with the byte holding
+/// each, as a scaffold for documenting them. Other items get nothing.
+pub fn flag_bits(item: &ItemLayout, bits: usize) -> String {
+    let field = match &item.shape {
+        Shape::Struct(x) => match x.fields.as_slice() {
+            [field] if field.name == "0" => field,
+            _ => return String::new(),
+        },
+        _ => return String::new(),
+    };
+    let ty = tokens_to_string(&field.ty);
+    if !INTEGERS.contains(&ty.as_str()) {
+        return String::new();
+    }
+    let mut out = String::new();
+    let available = 8 * field.layout.size();
+    if bits > available {
+        writeln!(
+            out,
+            "warning: `{}` only has {} bits, not {}",
+            ty, available, bits
+        )
+        .unwrap();
+    }
+    let bits = bits.min(available);
+    writeln!(out, "bits of `{}`:", item.path).unwrap();
+    let width = (bits.max(1) - 1).to_string().len();
+    for bit in 0..bits {
+        writeln!(
+            out,
+            "  bit {:>width$} (byte {})",
+            bit,
+            bit / 8,
+            width = width
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// Draws one character per byte of a struct: the first character of the field occupying it, or
+/// `.` for padding, with one cache line per row.
+pub fn bytemap(item: &ItemLayout) -> String {
+    let layout = match &item.shape {
+        Shape::Struct(x) => x,
+        _ => return String::new(),
+    };
+    let mut bytes = vec!['.'; layout.total.size()];
+    for field in &layout.fields {
+        let c = field.name.chars().next().unwrap_or('?');
+        for byte in &mut bytes[field.offset..field.offset + field.layout.size()] {
+            *byte = c;
+        }
+    }
+    let mut out = String::new();
+    writeln!(out, "bytemap:").unwrap();
+    for (i, line) in bytes.chunks(CACHE_LINE_SIZE).enumerate() {
+        writeln!(
+            out,
+            "  {:>6}  {}",
+            i * CACHE_LINE_SIZE,
+            line.iter().collect::<String>()
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// Groups structs whose fields have the same offsets, sizes, and alignments. `stable` says which
+/// items have a guaranteed layout, e.g. from `repr(C)`; others are only grouped when their field
+/// types are identical too, since rustc may lay out different types differently.
+pub fn layout_compatible(items: &[ItemLayout], stable: impl Fn(&ItemLayout) -> bool) -> String {
+    let mut groups: Vec<(Option<Vec<String>>, Vec<&ItemLayout>)> = Vec::new();
+    for item in items {
+        let layout = match &item.shape {
+            Shape::Struct(x) if !x.fields.is_empty() => x,
+            _ => continue,
+        };
+        let types = if stable(item) {
+            None
+        } else {
+            Some(
+                layout
+                    .fields
+                    .iter()
+                    .map(|x| tokens_to_string(&x.ty))
+                    .collect(),
+            )
+        };
+        match groups
+            .iter_mut()
+            .find(|(x, y)| *x == types && same_layout(y[0], item))
+        {
+            Some((_, group)) => group.push(item),
+            None => groups.push((types, vec![item])),
+        }
+    }
+    let groups: Vec<_> = groups.into_iter().filter(|(_, x)| x.len() > 1).collect();
+    if groups.is_empty() {
+        return String::new();
+    }
+    let mut out = String::new();
+    writeln!(out, "layout-compatible types:").unwrap();
+    for (_, group) in groups {
+        let names: Vec<String> = group.iter().map(|x| format!("`{}`", x.path)).collect();
+        writeln!(out, "  {}", names.join(", ")).unwrap();
+    }
+    writeln!(
+        out,
+        "note: only layouts fixed by `repr(C)` or `repr(transparent)` are guaranteed, so \
+         transmuting between other types relies on the current compiler"
+    )
+    .unwrap();
+    out
+}
+
+/// Describes how `change`, e.g. "removing `a` from `S`", would change a struct's layout.
+pub fn what_if(change: &str, delta: &LayoutDelta) -> String {
+    let mut out = match delta.size_delta() {
+        0 => format!("{} would keep its size of {}\n", change, delta.size_before),
+        x => format!(
+            "{} would change its size {} -> {} ({:+})\n",
+            change, delta.size_before, delta.size_after, x
+        ),
+    };
+    for (name, before, after) in &delta.moved {
+        writeln!(
+            out,
+            "  `{}` would move from offset {} to {}",
+            name, before, after
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// Lists the items that each of `items` embeds by value, from `Data::dependency_graph`.
+pub fn dependency_graph(
+    items: &[ItemLayout],
+    graph: &HashMap<parsed::TypePath, Vec<parsed::TypePath>>,
+) -> String {
+    let mut out = String::new();
+    for item in items {
+        if let Some(embedded) = graph.get(&item.path) {
+            let names: Vec<String> = embedded.iter().map(|x| format!("`{}`", x)).collect();
+            writeln!(out, "  `{}` -> {}", item.path, names.join(", ")).unwrap();
+        }
+    }
+    if out.is_empty() {
+        out
+    } else {
+        format!("embedded types:\n{}", out)
+    }
+}
+
+const LARGEST_COUNT: usize = 5;
+
+/// Buckets item sizes into `0-8`, `9-16`, `17-32`, ... and lists the largest items.
+pub fn histogram(items: &[ItemLayout]) -> String {
+    let mut buckets: Vec<usize> = Vec::new();
+    for item in items {
+        let size = item.total.size();
+        let bucket = if size <= 8 {
+            0
+        } else {
+            (usize::BITS - (size - 1).leading_zeros()) as usize - 3
+        };
+        if buckets.len() <= bucket {
+            buckets.resize(bucket + 1, 0);
+        }
+        buckets[bucket] += 1;
+    }
+
+    let mut out = String::new();
+    writeln!(out, "size histogram:").unwrap();
+    for (i, count) in buckets.iter().enumerate() {
+        let upper = 8 << i;
+        let lower = if i == 0 { 0 } else { (upper >> 1) + 1 };
+        let line = format!(
+            "  {:>12}  {:>4}  {}",
+            format!("{}-{}", lower, upper),
+            count,
+            "#".repeat(*count)
+        );
+        writeln!(out, "{}", line.trim_end()).unwrap();
+    }
+
+    let mut largest: Vec<&ItemLayout> = items.iter().collect();
+    largest.sort_by_key(|x| std::cmp::Reverse(x.total.size()));
+    writeln!(out, "largest types:").unwrap();
+    for item in largest.into_iter().take(LARGEST_COUNT) {
+        writeln!(out, "  {:>6}  {}", item.total.size(), item.path).unwrap();
+    }
+    out
+}
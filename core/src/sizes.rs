@@ -0,0 +1,197 @@
+//! Loading of user-supplied layouts from a sizes file.
+//!
+//! The file uses a small subset of TOML: each non-empty line maps a type path to an inline table,
+//! e.g. `"libc::timespec" = { size = 16, align = 8 }`, or registers a type-level number like
+//! those of `typenum`, e.g. `"typenum::U16" = { value = 16 }`. A `niche` key gives the number of
+//! invalid values of a type, which enclosing enums like `Option` can use for their tag, e.g.
+//! `"Handle" = { size = 4, align = 4, niche = 1 }`, or `"Fd" = { niche = 1 }` for an item whose
+//! layout pahole computes. `#` starts a comment.
+
+use std::{alloc::Layout, path::PathBuf};
+
+use syn::{parse::Error, LitStr};
+
+use crate::parsed;
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct Entry {
+    pub path: String,
+    pub fields: Vec<(String, u64)>,
+}
+
+/// What a line of the sizes file registers for its type.
+#[derive(Debug, Eq, PartialEq)]
+pub enum Registered {
+    /// A layout, and the number of invalid values of the type.
+    Layout(Layout, u128),
+    /// The number of invalid values of a type whose layout is computed.
+    Niche(u128),
+    /// The number a type stands for when used as a generic argument, like `typenum::U16`.
+    Number(usize),
+}
+
+struct Cursor<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Cursor<'a> {
+    fn skip_whitespace(&mut self) {
+        self.rest = self.rest.trim_start();
+    }
+
+    fn eat(&mut self, c: char) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.rest.strip_prefix(c) {
+            Some(rest) => {
+                self.rest = rest;
+                Ok(())
+            }
+            None => Err(format!("expected `{}`", c)),
+        }
+    }
+
+    fn key(&mut self) -> Result<String, String> {
+        self.skip_whitespace();
+        if let Some(rest) = self.rest.strip_prefix('"') {
+            let end = rest.find('"').ok_or("unterminated string")?;
+            self.rest = &rest[end + 1..];
+            return Ok(rest[..end].to_string());
+        }
+        let end = self
+            .rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+            .unwrap_or(self.rest.len());
+        if end == 0 {
+            return Err("expected a key".to_string());
+        }
+        let (key, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        Ok(key.to_string())
+    }
+
+    fn integer(&mut self) -> Result<u64, String> {
+        self.skip_whitespace();
+        let end = self
+            .rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '_'))
+            .unwrap_or(self.rest.len());
+        let (digits, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        digits
+            .replace('_', "")
+            .parse()
+            .map_err(|_| "expected an integer".to_string())
+    }
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn parse_line(line: &str) -> Result<Option<Entry>, String> {
+    let mut cursor = Cursor {
+        rest: strip_comment(line),
+    };
+    cursor.skip_whitespace();
+    if cursor.rest.is_empty() {
+        return Ok(None);
+    }
+    let path = cursor.key()?;
+    cursor.eat('=')?;
+    cursor.eat('{')?;
+    let mut fields = Vec::new();
+    loop {
+        cursor.skip_whitespace();
+        if cursor.eat('}').is_ok() {
+            break;
+        }
+        if !fields.is_empty() {
+            cursor.eat(',')?;
+        }
+        let key = cursor.key()?;
+        cursor.eat('=')?;
+        fields.push((key, cursor.integer()?));
+    }
+    cursor.skip_whitespace();
+    if !cursor.rest.is_empty() {
+        return Err("unexpected trailing characters".to_string());
+    }
+    Ok(Some(Entry { path, fields }))
+}
+
+/// Parses the contents of a sizes file, reporting errors with their 1-based line number.
+pub fn parse(text: &str) -> Result<Vec<Entry>, String> {
+    let mut entries = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        if let Some(entry) = parse_line(line).map_err(|err| format!("line {}: {}", i + 1, err))? {
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
+}
+
+pub fn entry_registration(entry: &Entry) -> Result<(parsed::TypePath, Registered), String> {
+    let path = syn::parse_str(&entry.path)
+        .map_err(|err| format!("`{}` is not a valid type path: {}", entry.path, err))?;
+    if let [(key, value)] = entry.fields.as_slice() {
+        match key.as_str() {
+            "value" => return Ok((parsed::TypePath(path), Registered::Number(*value as usize))),
+            "niche" => return Ok((parsed::TypePath(path), Registered::Niche(*value as u128))),
+            _ => {}
+        }
+    }
+    let mut size = None;
+    let mut align = None;
+    let mut niche = 0;
+    for (key, value) in &entry.fields {
+        match key.as_str() {
+            "size" => size = Some(*value),
+            "align" => align = Some(*value),
+            "niche" => niche = *value as u128,
+            _ => return Err(format!("unknown key `{}` for `{}`", key, entry.path)),
+        }
+    }
+    let size = size.ok_or_else(|| format!("missing `size` for `{}`", entry.path))?;
+    let align = align.ok_or_else(|| format!("missing `align` for `{}`", entry.path))?;
+    let layout = Layout::from_size_align(size as usize, align as usize)
+        .map_err(|_| format!("invalid layout for `{}`", entry.path))?;
+    Ok((parsed::TypePath(path), Registered::Layout(layout, niche)))
+}
+
+/// Reads the entries of the file named by `file`, relative to `CARGO_MANIFEST_DIR`, returning its
+/// full path too.
+pub fn read(file: &LitStr) -> Result<(PathBuf, Vec<Entry>), Error> {
+    let mut path = std::env::var_os("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_default();
+    path.push(file.value());
+    let text = std::fs::read_to_string(&path).map_err(|err| {
+        Error::new_spanned(
+            file,
+            format!("could not read `{}`: {}", path.display(), err),
+        )
+    })?;
+    let entries = parse(&text)
+        .map_err(|err| Error::new_spanned(file, format!("`{}`: {}", path.display(), err)))?;
+    Ok((path, entries))
+}
+
+/// Reads the sizes file named by `file`, relative to `CARGO_MANIFEST_DIR`.
+pub fn load(file: &LitStr) -> Result<Vec<(parsed::TypePath, Registered)>, Error> {
+    let (path, entries) = read(file)?;
+    entries
+        .iter()
+        .map(|entry| {
+            entry_registration(entry)
+                .map_err(|err| Error::new_spanned(file, format!("`{}`: {}", path.display(), err)))
+        })
+        .collect()
+}
@@ -0,0 +1,46 @@
+//! Layouts of common standard library structs.
+//!
+//! These mirror the current std implementation on mainstream platforms, which isn't guaranteed
+//! and may change between Rust versions, so reports mention when they're used.
+
+pub struct StdLayout {
+    pub path: &'static str,
+    pub size: usize,
+    pub align: usize,
+    pub niche: u128,
+}
+
+/// Nanosecond counts are stored as a `u32` below one billion.
+const NANOS_NICHE: u128 = (1 << 32) - 1_000_000_000;
+
+pub const STD_LAYOUTS: &[StdLayout] = &[
+    StdLayout {
+        path: "std::time::Duration",
+        size: 16,
+        align: 8,
+        niche: NANOS_NICHE,
+    },
+    StdLayout {
+        path: "core::time::Duration",
+        size: 16,
+        align: 8,
+        niche: NANOS_NICHE,
+    },
+    // Both are a `timespec` on Unix.
+    StdLayout {
+        path: "std::time::Instant",
+        size: 16,
+        align: 8,
+        niche: NANOS_NICHE,
+    },
+    StdLayout {
+        path: "std::time::SystemTime",
+        size: 16,
+        align: 8,
+        niche: NANOS_NICHE,
+    },
+];
+
+pub fn is_std_layout(path: &str) -> bool {
+    STD_LAYOUTS.iter().any(|x| x.path == path)
+}
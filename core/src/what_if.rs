@@ -0,0 +1,174 @@
+//! Previewing how changing a struct's fields or `repr` would change its layout, for the
+//! `what_if_added`, `what_if_removed` and `as_repr` options.
+
+use syn::{parse::Error, Type};
+
+use crate::{layout, parsed, Data};
+
+/// How a struct's layout changes when one of its fields is added or removed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LayoutDelta {
+    pub size_before: usize,
+    pub size_after: usize,
+    /// The fields present in both layouts whose offset changed, as `(name, before, after)`, named
+    /// as they were before the change.
+    pub moved: Vec<(String, usize, usize)>,
+}
+
+impl LayoutDelta {
+    pub fn size_delta(&self) -> isize {
+        self.size_after as isize - self.size_before as isize
+    }
+}
+
+fn struct_layout(layout: &layout::ItemLayout) -> &layout::StructLayout {
+    match &layout.shape {
+        layout::Shape::Struct(x) => x,
+        _ => unreachable!("only structs are modified"),
+    }
+}
+
+fn not_a_struct(path: &parsed::TypePath) -> Error {
+    Error::new(
+        proc_macro2::Span::call_site(),
+        format!("`{}` is not a struct", path),
+    )
+}
+
+impl Data {
+    /// The effect of appending a field `name: ty` to the struct at `path`. Tuple structs ignore
+    /// `name`.
+    pub fn what_if_field_added(
+        &mut self,
+        path: &parsed::TypePath,
+        name: &str,
+        ty: Type,
+    ) -> Result<LayoutDelta, Error> {
+        let duplicate = || {
+            Error::new(
+                proc_macro2::Span::call_site(),
+                format!("`{}` already has a field `{}`", path, name),
+            )
+        };
+        self.what_if(path, |fields| {
+            let ident = || {
+                syn::parse_str::<syn::Ident>(name).map_err(|_| {
+                    Error::new(
+                        proc_macro2::Span::call_site(),
+                        format!("`{}` is not a valid field name", name),
+                    )
+                })
+            };
+            match fields {
+                parsed::Struct::Unit => *fields = parsed::Struct::Struct(vec![(ident()?, ty)]),
+                parsed::Struct::Tuple(x) => x.push(ty),
+                parsed::Struct::Struct(x) => {
+                    if x.iter().any(|(y, _)| y == name) {
+                        return Err(duplicate());
+                    }
+                    x.push((ident()?, ty))
+                }
+            }
+            Ok(None)
+        })
+    }
+
+    /// The effect of removing the field `name` (or tuple index) from the struct at `path`.
+    pub fn what_if_field_removed(
+        &mut self,
+        path: &parsed::TypePath,
+        name: &str,
+    ) -> Result<LayoutDelta, Error> {
+        let missing = || {
+            Error::new(
+                proc_macro2::Span::call_site(),
+                format!("`{}` has no field `{}`", path, name),
+            )
+        };
+        self.what_if(path, |fields| match fields {
+            parsed::Struct::Unit => Err(missing()),
+            parsed::Struct::Tuple(x) => {
+                let index: usize = name.parse().map_err(|_| missing())?;
+                if index >= x.len() {
+                    return Err(missing());
+                }
+                x.remove(index);
+                Ok(Some(index))
+            }
+            parsed::Struct::Struct(x) => {
+                let index = x.iter().position(|(y, _)| y == name).ok_or_else(missing)?;
+                x.remove(index);
+                Ok(None)
+            }
+        })
+    }
+
+    /// The layout the struct at `path` would have with `repr` instead of its own.
+    pub fn layout_with_repr(
+        &mut self,
+        path: &parsed::TypePath,
+        repr: parsed::Repr,
+    ) -> Result<layout::ItemLayout, Error> {
+        let fields = match self.unprocessed_items.get(path) {
+            Some(parsed::Item::Struct(fields, _)) => fields.clone(),
+            _ => return Err(not_a_struct(path)),
+        };
+        self.modified_layout(path, parsed::Item::Struct(fields, repr))
+    }
+
+    /// Lays out `item` in place of the item at `path`, leaving the original in place afterwards.
+    fn modified_layout(
+        &mut self,
+        path: &parsed::TypePath,
+        item: parsed::Item,
+    ) -> Result<layout::ItemLayout, Error> {
+        let original = self.unprocessed_items.insert(path.clone(), item).unwrap();
+        let layout = self.item_layout(path);
+        self.unprocessed_items.insert(path.clone(), original);
+        layout
+    }
+
+    /// Lays out the struct at `path` before and after applying `modify` to a copy of its fields.
+    /// `modify` returns the index it removed from a tuple struct, after which the later fields are
+    /// numbered one lower.
+    fn what_if(
+        &mut self,
+        path: &parsed::TypePath,
+        modify: impl FnOnce(&mut parsed::Struct) -> Result<Option<usize>, Error>,
+    ) -> Result<LayoutDelta, Error> {
+        let (mut modified, repr) = match self.unprocessed_items.get(path) {
+            Some(parsed::Item::Struct(fields, repr)) => (fields.clone(), repr.clone()),
+            _ => return Err(not_a_struct(path)),
+        };
+        let removed_index = modify(&mut modified)?;
+        // The name each field of the original struct has afterwards, if it still exists.
+        let renamed = |name: &str| match (removed_index, name.parse::<usize>()) {
+            (Some(removed), Ok(index)) if index == removed => None,
+            (Some(removed), Ok(index)) if index > removed => Some((index - 1).to_string()),
+            _ => Some(name.to_string()),
+        };
+
+        let before = self.item_layout(path)?;
+        let after = self.modified_layout(path, parsed::Item::Struct(modified, repr))?;
+
+        let after_fields = &struct_layout(&after).fields;
+        let moved = struct_layout(&before)
+            .fields
+            .iter()
+            .filter_map(|field| {
+                let name = renamed(&field.name)?;
+                let new = after_fields.iter().find(|x| x.name == name)?;
+                if new.offset != field.offset {
+                    Some((field.name.clone(), field.offset, new.offset))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        Ok(LayoutDelta {
+            size_before: before.total.size(),
+            size_after: after.total.size(),
+            moved,
+        })
+    }
+}
@@ -1,4 +1,6 @@
-use pahole_macro::pahole;
+#![allow(dead_code, non_snake_case)]
+
+use pahole_macro::{pahole, pahole_type};
 
 #[pahole]
 struct A {
@@ -20,3 +22,334 @@ mod M {
         C = 9,
     }
 }
+
+#[pahole(histogram)]
+mod histogram {
+    struct Small(u8);
+    struct Large {
+        a: [u64; 4],
+        b: (u8, u32),
+    }
+}
+
+#[pahole]
+struct Node {
+    val: u32,
+    next: Option<Box<Self>>,
+}
+
+mod ffi {
+    pub struct Opaque([u32; 3]);
+}
+
+struct Handle(u64);
+
+#[pahole(sizes = "tests/sizes.toml")]
+struct WithSizes {
+    inner: ffi::Opaque,
+    handle: Handle,
+}
+
+#[pahole]
+const ORIGIN: (u8, u32) = (0, 0);
+
+#[pahole]
+static TABLE: [u16; 3] = [1, 2, 3];
+
+#[pahole]
+mod reprs {
+    #[repr(C)]
+    struct C {
+        a: u8,
+        b: u32,
+    }
+
+    #[repr(C, packed(2))]
+    struct Packed {
+        a: u8,
+        b: u32,
+    }
+
+    #[repr(C, align(16))]
+    union Aligned {
+        a: u16,
+        b: [u8; 3],
+    }
+}
+
+#[pahole(pointer_width = 32)]
+struct Slice32 {
+    data: &'static [u8],
+    len: usize,
+}
+
+#[pahole(bytemap)]
+struct Mapped {
+    flag: bool,
+    value: u64,
+    small: u16,
+}
+
+#[pahole]
+mod reexport {
+    mod inner {
+        pub struct Thing(pub u32, pub u8);
+    }
+    pub use inner::Thing;
+
+    mod user {
+        struct User {
+            thing: crate::reexport::Thing,
+        }
+    }
+}
+
+#[pahole]
+mod std_layouts {
+    use std::time::Duration;
+
+    pub struct Timeout {
+        after: Duration,
+        started: std::time::Instant,
+    }
+}
+
+#[pahole]
+struct TraitObjects {
+    obj: Box<dyn std::fmt::Debug>,
+    callback: &'static dyn Fn(),
+}
+
+#[pahole(only(Focused))]
+mod only {
+    pub struct Dependency(u8, u32);
+    pub struct Focused(Dependency, u16);
+    pub struct Unrelated(u64);
+}
+
+#[pahole(aos_soa(1024))]
+struct Particle {
+    alive: bool,
+    position: [f32; 3],
+    kind: u16,
+}
+
+#[pahole(emit_docs)]
+mod emit_docs {
+    /// Has its layout in its docs.
+    pub struct Documented {
+        a: u8,
+        b: u32,
+    }
+}
+
+#[pahole]
+struct WithDefault<T = u32> {
+    value: T,
+    flag: bool,
+}
+
+#[pahole]
+mod externs {
+    extern "C" {
+        static environ: *const *const u8;
+        fn getenv(name: *const u8) -> *const u8;
+    }
+}
+
+#[pahole(label = "hot-path")]
+struct Labelled {
+    hits: u64,
+}
+
+#[pahole(as_repr = "C, packed")]
+struct AsRepr {
+    a: u8,
+    b: u32,
+}
+
+#[pahole]
+mod generic_alias {
+    pub struct Wrapper<T> {
+        value: T,
+        tag: u8,
+    }
+
+    pub type Handle = Wrapper<u32>;
+}
+
+#[pahole]
+#[repr(C, u8)]
+enum TaggedUnion {
+    Empty,
+    Wide(u64),
+    Narrow(u8),
+}
+
+#[pahole(flag_bits = 8)]
+struct Flags(u8);
+
+#[pahole(size = 16, align = 8)]
+struct PacketHeader {
+    kind: u32,
+    length: u32,
+    offset: u64,
+}
+
+#[pahole]
+union TextOrNumber {
+    text: std::mem::ManuallyDrop<String>,
+    number: u64,
+}
+
+#[pahole(unknown = "pointer")]
+struct UsesForeignTypes {
+    lookup: std::collections::HashMap<u32, u32>,
+    id: u32,
+}
+
+#[pahole]
+#[repr(C)]
+struct VTable {
+    open: Option<extern "C" fn(*const u8) -> i32>,
+    close: Option<extern "C" fn()>,
+}
+
+#[pahole]
+#[repr(C)]
+struct ReservedBytes {
+    version: u8,
+    #[pahole_hole(3)]
+    length: u32,
+}
+
+#[pahole(emit_table_macro)]
+#[repr(C)]
+struct Queried {
+    a: u16,
+    b: u32,
+}
+
+const _: () = assert!(layout_of_Queried!(offset b) == std::mem::offset_of!(Queried, b));
+const _: () = assert!(layout_of_Queried!(size) == std::mem::size_of::<Queried>());
+
+#[pahole(format = "compact")]
+struct Grepped {
+    a: u64,
+    b: u8,
+}
+
+#[pahole(monomorphize("Buf<16>"))]
+struct Buf<const N: usize> {
+    len: u32,
+    data: [u8; N],
+}
+
+#[pahole(c_layout = "tests/c_layout.toml")]
+#[repr(C)]
+struct Record {
+    id: u32,
+    value: f64,
+}
+
+#[pahole]
+#[repr(C)]
+struct Point(f32, f32, f64);
+
+#[pahole(emit_mod)]
+#[repr(C)]
+struct Exported {
+    a: u16,
+    b: u64,
+}
+
+const _: () = assert!(Exported_layout::SIZE == std::mem::size_of::<Exported>());
+const _: () = assert!(Exported_layout::offsets::b == std::mem::offset_of!(Exported, b));
+
+#[pahole(show_packed_baseline, field_percentages)]
+struct Unpacked {
+    a: u8,
+    b: u32,
+}
+
+#[pahole(symbolic_ptr)]
+struct Slice {
+    len: usize,
+    flag: u8,
+}
+
+#[pahole]
+#[repr(C, align(16))]
+union AlignedUnion {
+    a: std::mem::ManuallyDrop<String>,
+    b: u128,
+}
+
+#[pahole]
+mod tagged {
+    #[repr(C)]
+    pub union Value {
+        i: u32,
+        f: f64,
+    }
+
+    #[repr(C)]
+    pub struct Tagged {
+        tag: u8,
+        value: Value,
+    }
+}
+
+#[pahole(size = 4096, align = 1)]
+struct IoBuffer(std::mem::MaybeUninit<[u8; 4096]>);
+
+#[pahole(emit_mod)]
+struct Borrowing {
+    name: std::borrow::Cow<'static, str>,
+    data: std::borrow::Cow<'static, [u8]>,
+}
+
+const _: () = assert!(Borrowing_layout::SIZE == std::mem::size_of::<Borrowing>());
+
+#[pahole(require_ffi_safe)]
+mod ffi_boundary {
+    #[repr(C)]
+    pub struct Request {
+        id: u32,
+        payload: *const u8,
+        len: usize,
+    }
+
+    #[repr(transparent)]
+    pub struct Handle(u64);
+}
+
+#[pahole(dependency_graph)]
+mod embedded {
+    struct Point {
+        x: f32,
+        y: f32,
+    }
+
+    struct Segment {
+        from: Point,
+        to: Point,
+        next: Option<Box<Segment>>,
+    }
+}
+
+#[pahole(
+    what_if_added("capacity::Packet.urgent: bool"),
+    what_if_removed("capacity::Packet.checksum")
+)]
+mod capacity {
+    #[repr(C)]
+    struct Packet {
+        len: u32,
+        checksum: u16,
+        flags: u16,
+    }
+}
+
+pahole_type!((u8, u64));
+pahole_type!([[u16; 3]; 2]);